@@ -0,0 +1,276 @@
+//!
+//! # Cache section
+//!
+//! This module persists discovered candidates per configured path, so the picker doesn't have to
+//! wait for the filesystem on every start.
+//!
+//! Entries are invalidated when the signature of their path changes. It is a hash of the
+//! modification times of the path and the directories below it, down to one level deeper than
+//! scanned, so new projects and new repositories in existing directories are noticed. A glob
+//! pattern is cached as a whole, its signature covers all of its matches. The file is a
+//! plain tab separated list: a `@<kind>\t<root>\t<signature>` header per entry followed by one
+//! `<name>\t<path>` line per candidate.
+
+use crate::{
+    config::{self, Root},
+    error::{Context, Result},
+};
+use std::{
+    collections::{HashMap, hash_map::DefaultHasher},
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+#[derive(Debug, Clone)]
+struct Entry {
+    signature: u64,
+    dirs: Vec<(String, PathBuf)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cache {
+    entries: HashMap<(String, PathBuf), Entry>,
+}
+
+impl Cache {
+    fn file() -> Result<PathBuf> {
+        Ok(config::cache_dir()
+            .context("Error getting cache directory")?
+            .join("candidates"))
+    }
+
+    pub(crate) fn load() -> Result<Cache> {
+        let file = Cache::file()?;
+        if !file.try_exists()? {
+            return Ok(Cache::default());
+        }
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Error reading '{}'", file.display()))?;
+
+        Ok(Cache::parse(&content))
+    }
+
+    fn parse(content: &str) -> Cache {
+        let mut cache = Cache::default();
+        let mut current: Option<(String, PathBuf)> = None;
+        for line in content.lines() {
+            if let Some(header) = line.strip_prefix('@') {
+                let mut parts = header.splitn(3, '\t');
+                let (Some(kind), Some(root), Some(signature)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    current = None;
+                    continue;
+                };
+                let key = (kind.to_string(), PathBuf::from(root));
+                cache.entries.insert(
+                    key.clone(),
+                    Entry {
+                        signature: signature.parse().unwrap_or_default(),
+                        dirs: Vec::new(),
+                    },
+                );
+                current = Some(key);
+            } else if let (Some(key), Some((name, path))) = (&current, line.split_once('\t'))
+                && let Some(entry) = cache.entries.get_mut(key)
+            {
+                entry.dirs.push((name.to_string(), PathBuf::from(path)));
+            }
+        }
+
+        cache
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        let file = Cache::file()?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Error creating '{}'", dir.display()))?;
+        }
+
+        // Write to a temporary file first, so an interrupted save can't leave a truncated cache.
+        let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, self.serialize())
+            .with_context(|| format!("Error writing '{}'", tmp.display()))?;
+        fs::rename(&tmp, &file).with_context(|| format!("Error replacing '{}'", file.display()))?;
+
+        Ok(())
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = String::new();
+        for ((kind, root), entry) in &self.entries {
+            content.push_str(&format!(
+                "@{kind}\t{}\t{}\n",
+                root.display(),
+                entry.signature
+            ));
+            for (name, path) in &entry.dirs {
+                content.push_str(&format!("{name}\t{}\n", path.display()));
+            }
+        }
+
+        content
+    }
+
+    /// Returns the signature and cached candidates of `kind` for `root`, regardless of whether
+    /// they are stale.
+    pub(crate) fn get(&self, kind: &str, root: &Path) -> Option<(u64, Vec<(String, PathBuf)>)> {
+        self.entries
+            .get(&(kind.to_string(), root.to_path_buf()))
+            .map(|entry| (entry.signature, entry.dirs.clone()))
+    }
+
+    /// Drops the entries of paths that aren't one of `roots` anymore.
+    pub(crate) fn retain_roots(&mut self, roots: &[Root]) {
+        self.entries
+            .retain(|(_, path), _| roots.iter().any(|root| root.path == *path));
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        kind: &str,
+        root: &Path,
+        signature: u64,
+        dirs: Vec<(String, PathBuf)>,
+    ) {
+        self.entries.insert(
+            (kind.to_string(), root.to_path_buf()),
+            Entry { signature, dirs },
+        );
+    }
+}
+
+/// Hashes the modification times of `roots` and the directories below them, down to one level
+/// deeper than `depth`.
+///
+/// Hidden directories and the contents of repositories are left out, they change all the time
+/// without affecting what is discovered.
+pub(crate) fn signature(roots: &[PathBuf], depth: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for root in roots {
+        hash_dir(root, depth + 1, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+fn hash_dir(dir: &Path, levels: usize, hasher: &mut DefaultHasher) {
+    dir.hash(hasher);
+    mtime(dir).hash(hasher);
+    if levels == 0 || dir.join(".git").exists() {
+        return;
+    }
+
+    let Ok(entries) = dir.read_dir() else {
+        return;
+    };
+    let mut child_dirs: Vec<PathBuf> = entries
+        .filter_map(std::result::Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .collect();
+    child_dirs.sort();
+    for child_dir in child_dirs {
+        hash_dir(&child_dir, levels - 1, hasher);
+    }
+}
+
+fn mtime(root: &Path) -> u128 {
+    fs::metadata(root)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_entries() {
+        let cache =
+            Cache::parse("@repos\t/code\t42\nfoo\t/code/foo\nbar\t/code/bar\n@dirs/2\t/notes\t7\n");
+
+        assert_eq!(
+            cache.get("repos", Path::new("/code")),
+            Some((
+                42,
+                vec![
+                    ("foo".to_string(), PathBuf::from("/code/foo")),
+                    ("bar".to_string(), PathBuf::from("/code/bar")),
+                ]
+            ))
+        );
+        assert_eq!(
+            cache.get("dirs/2", Path::new("/notes")),
+            Some((7, Vec::new()))
+        );
+        assert_eq!(cache.get("dirs", Path::new("/notes")), None);
+    }
+
+    #[test]
+    fn parse_skips_malformed_lines() {
+        let cache = Cache::parse("orphan\t/before/header\n@broken\nfoo\t/foo\n@repos\t/code\tx\n");
+
+        assert_eq!(cache.get("broken", Path::new("")), None);
+        assert_eq!(
+            cache.get("repos", Path::new("/code")),
+            Some((0, Vec::new()))
+        );
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        let mut cache = Cache::default();
+        cache.insert(
+            "worktrees",
+            Path::new("/work"),
+            u64::MAX,
+            vec![("repo/main".to_string(), PathBuf::from("/work/repo/main"))],
+        );
+        cache.insert("dirs", Path::new("/empty"), 1, Vec::new());
+
+        let parsed = Cache::parse(&cache.serialize());
+
+        assert_eq!(
+            parsed.get("worktrees", Path::new("/work")),
+            cache.get("worktrees", Path::new("/work"))
+        );
+        assert_eq!(
+            parsed.get("dirs", Path::new("/empty")),
+            Some((1, Vec::new()))
+        );
+    }
+
+    #[test]
+    fn retain_roots_drops_unconfigured_paths() {
+        let mut cache = Cache::default();
+        cache.insert("repos", Path::new("/kept"), 1, Vec::new());
+        cache.insert("dirs/1", Path::new("/kept"), 2, Vec::new());
+        cache.insert("repos", Path::new("/removed"), 3, Vec::new());
+        let root = Root {
+            path: PathBuf::from("/kept"),
+            kinds: Vec::new(),
+            depth: 0,
+            prefix: String::new(),
+        };
+
+        cache.retain_roots(&[root]);
+
+        assert_eq!(
+            cache.get("repos", Path::new("/kept")),
+            Some((1, Vec::new()))
+        );
+        assert_eq!(
+            cache.get("dirs/1", Path::new("/kept")),
+            Some((2, Vec::new()))
+        );
+        assert_eq!(cache.get("repos", Path::new("/removed")), None);
+    }
+}
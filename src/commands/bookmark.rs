@@ -0,0 +1,63 @@
+//!
+//! # Bookmark handler
+//!
+//! This module handles the logic to manage bookmarked projects, which are offered by `open`
+//! regardless of the configured paths.
+
+use crate::{
+    config_file::ConfigFile,
+    error::{Context, Error, Result},
+    utils,
+};
+use log::info;
+
+/// Bookmarks `path`, a relative path is made absolute first.
+///
+/// # Errors
+///
+/// Will return `Err` if the config file can't be read or written.
+pub fn add(path: &str, dry_run: bool) -> Result<()> {
+    let path = utils::absolute_path(path)?;
+    let mut config_file = ConfigFile::load().context("Error loading config file")?;
+    if config_file.values("bookmark").contains(&path.as_str()) {
+        info!("'{path}' is already bookmarked");
+        return Ok(());
+    }
+
+    config_file.add("bookmark", &path);
+    config_file
+        .save(dry_run)
+        .context("Error saving config file")
+}
+
+/// Removes the bookmark of `path`.
+///
+/// # Errors
+///
+/// Will return `Err` if `path` isn't bookmarked or the config file can't be read or written.
+pub fn remove(path: &str, dry_run: bool) -> Result<()> {
+    let path = utils::absolute_path(path)?;
+    let mut config_file = ConfigFile::load().context("Error loading config file")?;
+    if config_file.remove("bookmark", |value| value == path) == 0 {
+        return Err(Error::Message(format!("'{path}' isn't bookmarked")));
+    }
+
+    config_file
+        .save(dry_run)
+        .context("Error saving config file")
+}
+
+/// Returns all bookmarked paths as they are written in the config file.
+///
+/// # Errors
+///
+/// Will return `Err` if the config file can't be read.
+pub fn list() -> Result<Vec<String>> {
+    let config_file = ConfigFile::load().context("Error loading config file")?;
+
+    Ok(config_file
+        .values("bookmark")
+        .into_iter()
+        .map(ToString::to_string)
+        .collect())
+}
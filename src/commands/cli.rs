@@ -3,9 +3,77 @@
 //!
 //! This module handles the CLI arguments using clap.
 
-use clap::{Arg, ArgAction, Command, ValueEnum, crate_version, value_parser};
-use clap_complete::{Generator, Shell, generate};
-use std::io;
+use crate::{
+    commands::{bookmark, paths, status},
+    error::{Context, Result},
+    sessionizer::{SearchMode, Sessionizer},
+};
+use clap::{Arg, ArgAction, Command, crate_version, value_parser};
+use clap_complete::{ArgValueCandidates, CompletionCandidate, Shell, env::Shells};
+use clap_mangen::Man;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Configuration read from the environment, shown by `--help` and in the man page.
+const ENVIRONMENT: &str = "\
+Environment:
+  SESSIONIZER_PATHS
+          ':'-separated paths searched for projects, the ones added with `paths add` follow them.
+          A path may start with '~', use $VAR or ${VAR} and be a glob pattern like ~/worktrees/*.
+          Options follow it separated by ';': kinds=dirs,repos,worktrees limits what it
+          contributes, depth=N scans N levels below it and prefix=P is prepended to every name,
+          e.g. '~/code;kinds=repos;depth=1;prefix=work/'
+
+  SESSIONIZER_SOURCES
+          Commands run with `sh -c`, one per line, whose output adds candidates. Every line of
+          output is either an absolute path or 'name<TAB>path'
+
+  SESSIONIZER_SCAN_TIMEOUT
+          Seconds after which scanning a path or running a source is given up [default: 2]
+
+  SESSIONIZER_DISPLAY_WARNINGS
+          Set to 1 to also show skipped paths and sources with `tmux display-message`
+
+  DEFAULT_SESSION
+          Name of the session `startup` creates and `kill` switches to [default: Default]
+
+  SESSIONIZER_LAYOUT
+          Layout of new project sessions [default: the editor and a shell named Bash]
+
+  SESSIONIZER_EDITOR
+          Editor run in the first window of the default project layout, $VISUAL and $EDITOR are
+          used if it isn't set
+
+  SESSIONIZER_DEFAULT_DIR, SESSIONIZER_DEFAULT_LAYOUT
+          Directory [default: $HOME] and layout [default: two shells] of the default session
+
+  SESSIONIZER_STARTUP_SESSIONS
+          ':'-separated sessions `startup` brings up in the background
+
+  SESSIONIZER_SESSION_<NAME>_DIR, SESSIONIZER_SESSION_<NAME>_LAYOUT
+          Directory and layout of the startup session NAME, which is uppercased with every other
+          character than A-Z and 0-9 replaced by '_' [default: the default session's directory
+          and a single shell]
+
+  SESSIONIZER_SESSION_ENV
+          'KEY=value' lines set in the environment of every new project session, next to
+          SESSIONIZER_ROOT, SESSIONIZER_KIND and SESSIONIZER_BRANCH
+
+  SESSIONIZER_DOTENV
+          Set to 1 to also load the project's .env file into the environment of new sessions
+
+  SESSIONIZER_HOOK_CREATE, SESSIONIZER_HOOK_SWITCH, SESSIONIZER_HOOK_KILL
+          Commands run with `sh -c` in the session's directory after a session was created,
+          before switching to a session and before killing one. They get SESSIONIZER_EVENT,
+          SESSIONIZER_SESSION, SESSIONIZER_PATH and SESSIONIZER_KIND in their environment. A
+          failing hook is reported, but doesn't stop the session from being entered or killed
+
+Layouts:
+  A layout is a ';'-separated list of windows in the form 'name=command', either part can be
+  left out. A window without a command runs a shell, e.g. 'editor=nvim .;shell;=htop' creates a
+  window named editor running nvim, one named shell and an unnamed one running htop";
 
 /// Function to create the CLI structure using clap
 #[must_use]
@@ -18,61 +86,301 @@ pub fn build_cli() -> Command {
             "Cli-tool which integrates with tmux to manage sessions based on project folders.
             It is intended to work well with git worktrees.",
         )
+        .after_long_help(ENVIRONMENT)
         .arg_required_else_help(true)
+        .subcommand_required(true)
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("Use verbose output, repeat for more detail")
+                .global(true)
+                .action(ArgAction::Count),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Append the log to PATH (given as --log-file=PATH), defaults to the XDG state directory")
+                .global(true)
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print the tmux commands that would be executed instead of running them")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
         .subcommand(
             Command::new("open")
                 .about("Open a new or switch to an existing session in tmux")
+                .arg(
+                    Arg::new("target")
+                        .help("Session or project to open directly, optionally followed by ':<window>'")
+                        .value_name("SESSION[:WINDOW]")
+                        .action(ArgAction::Set)
+                        .add(ArgValueCandidates::new(target_candidates)),
+                )
                 .arg(
                     Arg::new("search")
                         .short('s')
                         .long("search")
-                        .help("Configure what should be searchable from the given directories")
-                        .action(ArgAction::Set)
+                        .help("Configure what should be searchable, on top of what each path is configured to contribute, e.g. '-s repos,worktrees'")
+                        .action(ArgAction::Append)
                         .value_parser(value_parser!(SearchMode))
-                        .num_args(0..=3)
+                        .value_delimiter(',')
                         .default_value("all"),
                 )
                 .arg(
-                    Arg::new("verbose")
-                        .short('v')
-                        .long("verbose")
-                        .help("Use verbose output")
+                    Arg::new("refresh")
+                        .short('r')
+                        .long("refresh")
+                        .help("Rescan all configured paths instead of using cached results")
                         .action(ArgAction::SetTrue),
-                ),
+                )
+        )
+        .subcommand(Command::new("last").about("Switch to the previously used session"))
+        .subcommand(
+            Command::new("kill").about("Kill active session").arg(
+                Arg::new("session")
+                    .help("Session to kill instead of the active one")
+                    .action(ArgAction::Set)
+                    .add(ArgValueCandidates::new(session_candidates)),
+            ),
         )
-        .subcommand(Command::new("kill").about("Kill active session"))
         .subcommand(Command::new("kill-all").about("Kill all active sessions"))
-        .subcommand(Command::new("startup").about("Start tmux with the default session"))
-        .arg(
-            Arg::new("generator")
-                .short('G')
-                .long("generate")
-                .action(ArgAction::Set)
-                .value_parser(value_parser!(Shell)),
+        .subcommand(
+            Command::new("startup")
+                .about("Start tmux with the default session")
+                .arg(
+                    Arg::new("grouped")
+                        .short('g')
+                        .long("grouped")
+                        .help("Attach through a new grouped session if the default session is already running")
+                        .action(ArgAction::SetTrue),
+                ),
         )
+        .subcommand(
+            Command::new("status")
+                .about("Print a status line describing a session, e.g. for tmux's status-right")
+                .arg(
+                    Arg::new("session")
+                        .short('t')
+                        .long("session")
+                        .help("Session to describe instead of the active one, e.g. '#S' inside tmux")
+                        .action(ArgAction::Set)
+                        .add(ArgValueCandidates::new(session_candidates)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Format using {session}, {root}, {kind}, {branch}, {dirty} (slow), {worktree} and {others}")
+                        .action(ArgAction::Set)
+                        .default_value(status::DEFAULT_FORMAT),
+                ),
+        )
+        .subcommand(
+            Command::new("paths")
+                .about("Manage the paths searched for projects in the config file")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a path, optionally followed by ';kinds=...;depth=...;prefix=...'")
+                        .arg(
+                            Arg::new("path")
+                                .required(true)
+                                .value_name("PATH[;OPTIONS]")
+                                .action(ArgAction::Set),
+                        ),
+                )
+                .subcommand(
+                    Command::new("remove").about("Remove a path").arg(
+                        Arg::new("path")
+                            .required(true)
+                            .action(ArgAction::Set)
+                            .add(ArgValueCandidates::new(path_candidates)),
+                    ),
+                )
+                .subcommand(Command::new("list").about("List all configured paths")),
+        )
+        .subcommand(
+            Command::new("bookmark")
+                .about("Manage projects that are offered regardless of the configured paths")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add").about("Bookmark a project").arg(
+                        Arg::new("path")
+                            .help("Directory of the project, defaults to the current one")
+                            .action(ArgAction::Set)
+                            .default_value("."),
+                    ),
+                )
+                .subcommand(
+                    Command::new("remove").about("Remove a bookmark").arg(
+                        Arg::new("path")
+                            .help("Directory of the project, defaults to the current one")
+                            .action(ArgAction::Set)
+                            .default_value(".")
+                            .add(ArgValueCandidates::new(bookmark_candidates)),
+                    ),
+                )
+                .subcommand(Command::new("list").about("List all bookmarked projects")),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print the completion script for the given shell")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(Shell)),
+                ),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Generate man pages for rost_sessionizer and its subcommands")
+                .arg(
+                    Arg::new("out-dir")
+                        .short('o')
+                        .long("out-dir")
+                        .value_name("DIR")
+                        .help("Write a page per command into DIR instead of printing the main page")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+}
+
+/// Function to generate roff man pages from [`build_cli`].
+///
+/// Without `out_dir` the page of the main command is printed, otherwise a page for every command
+/// is written into `out_dir`.
+///
+/// # Errors
+///
+/// Will return `Err` if a page can't be written.
+pub fn print_man_pages(out_dir: Option<&Path>) -> Result<()> {
+    let cmd = build_cli();
+    match out_dir {
+        Some(out_dir) => {
+            std::fs::create_dir_all(out_dir)
+                .with_context(|| format!("Error creating '{}'", out_dir.display()))?;
+            clap_mangen::generate_to(cmd, out_dir)
+                .with_context(|| format!("Error writing man pages to '{}'", out_dir.display()))?;
+        }
+        None => Man::new(cmd)
+            .render(&mut io::stdout())
+            .context("Error writing man page")?,
+    }
+
+    Ok(())
+}
+
+/// Function to print the completion script for `shell`.
+///
+/// The script calls back into the binary, so session and project names are completed from what is
+/// currently running and configured.
+///
+/// # Errors
+///
+/// Will return `Err` if the shell isn't supported or the script can't be written.
+pub fn print_completions(shell: Shell) -> Result<()> {
+    let name = build_cli().get_name().to_string();
+    Shells::builtins()
+        .completer(&shell.to_string())
+        .with_context(|| format!("Completions for {shell} aren't supported"))?
+        .write_registration("COMPLETE", &name, &name, &name, &mut io::stdout())
+        .context("Error writing completion script")?;
+
+    Ok(())
 }
 
-/// Function to create output for bash completion
-pub fn print_completions<G: Generator>(generator: G, cmd: &mut Command) {
-    println!(
-        "-----------------------------------------------------------------------------------------------------"
-    );
-    generate(
-        generator,
-        cmd,
-        cmd.get_name().to_string(),
-        &mut io::stdout(),
-    );
-    println!(
-        "-----------------------------------------------------------------------------------------------------"
-    );
-    println!("Copy everything between the lines into the corresponding dir for the shell you use.");
+fn session_candidates() -> Vec<CompletionCandidate> {
+    Sessionizer::default()
+        .sessions()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
 }
 
-#[derive(Debug, Clone, ValueEnum)]
-pub enum SearchMode {
-    All,
-    Dirs,
-    Repos,
-    Worktrees,
+fn target_candidates() -> Vec<CompletionCandidate> {
+    // Completion has to be fast, so nothing is scanned and no source is run.
+    Sessionizer::default()
+        .cached_candidates()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|candidate| {
+            let help = candidate.path.map(|path| path.display().to_string().into());
+            CompletionCandidate::new(candidate.name).help(help)
+        })
+        .collect()
+}
+
+fn path_candidates() -> Vec<CompletionCandidate> {
+    paths::removable()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+fn bookmark_candidates() -> Vec<CompletionCandidate> {
+    bookmark::list()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_matches(args: &[&str]) -> (Vec<SearchMode>, Option<String>) {
+        let matches = build_cli()
+            .try_get_matches_from(["rost_sessionizer", "open"].iter().chain(args))
+            .unwrap();
+        let (_, open) = matches.subcommand().unwrap();
+
+        (
+            open.get_many::<SearchMode>("search")
+                .unwrap()
+                .copied()
+                .collect(),
+            open.get_one::<String>("target").cloned(),
+        )
+    }
+
+    #[test]
+    fn search_defaults_to_all() {
+        assert_eq!(open_matches(&[]), (vec![SearchMode::All], None));
+    }
+
+    #[test]
+    fn search_doesnt_take_the_target() {
+        assert_eq!(
+            open_matches(&["-s", "repos", "api:tests"]),
+            (vec![SearchMode::Repos], Some("api:tests".to_string()))
+        );
+    }
+
+    #[test]
+    fn search_accepts_lists_and_repetitions() {
+        assert_eq!(
+            open_matches(&["-s", "repos,worktrees", "--search", "sources", "api"]),
+            (
+                vec![
+                    SearchMode::Repos,
+                    SearchMode::Worktrees,
+                    SearchMode::Sources
+                ],
+                Some("api".to_string())
+            )
+        );
+    }
 }
@@ -3,27 +3,53 @@
 //!
 //! This module handles the logic to kill current or all sessions.
 
-use crate::{config, utils};
-use anyhow::{Context, Result};
+use crate::{
+    config,
+    error::{Context, Result},
+    sessionizer::{Options, Sessionizer},
+    utils,
+};
 
 /// # Errors
 ///
 /// Will return `Err` if any of the tmux operations fail.
-pub fn kill_current_session() -> Result<()> {
+pub fn kill_current_session(dry_run: bool) -> Result<()> {
     let current_session = utils::current_session().context("Error getting current session")?;
 
-    utils::tmux_switch_client(&config::default_session(), None)
-        .context("Error switching to default session")?;
+    kill_session(&current_session, dry_run)
+        .with_context(|| format!("Error killing current session: '{current_session}'"))
+}
+
+/// Kills `session`, switching to the default session first if it is the active one.
+///
+/// # Errors
+///
+/// Will return `Err` if any of the tmux operations fail.
+pub fn kill_session(session: &str, dry_run: bool) -> Result<()> {
+    let current_session = if utils::inside_tmux() {
+        Some(utils::current_session().context("Error getting current session")?)
+    } else {
+        None
+    };
+
+    if current_session.as_deref() == Some(session) {
+        utils::tmux_switch_client(&config::default_session(), None, dry_run)
+            .context("Error switching to default session")?;
+    }
 
-    if current_session == config::default_session() {
-        utils::tmux_display_message(&format!(
-            "Can't kill the default session: '{}'",
-            config::default_session()
-        ))
+    if session == config::default_session() {
+        utils::tmux_display_message(
+            &format!(
+                "Can't kill the default session: '{}'",
+                config::default_session()
+            ),
+            dry_run,
+        )
         .context("Error sending 'Can't kill the default session' notification")?;
     } else {
-        utils::tmux_kill_session(&current_session)
-            .with_context(|| format!("Error killing current session: '{current_session}'"))?;
+        sessionizer(dry_run)
+            .kill(session)
+            .with_context(|| format!("Error killing session: '{session}'"))?;
     }
 
     Ok(())
@@ -32,18 +58,30 @@ pub fn kill_current_session() -> Result<()> {
 /// # Errors
 ///
 /// Will return `Err` if the existing sessions can't be found or any of the tmux operations fail.
-pub fn kill_all_sessions() -> Result<()> {
+pub fn kill_all_sessions(dry_run: bool) -> Result<()> {
     let mut sessions =
         utils::existing_session_names().context("Error getting existing session names")?;
     sessions.retain(|s| !s.contains(&config::default_session()));
 
-    utils::tmux_switch_client(&config::default_session(), None)
-        .context("Error switching to default session")?;
+    // Only a client inside tmux can be switched, from a plain terminal there is none.
+    if utils::inside_tmux() {
+        utils::tmux_switch_client(&config::default_session(), None, dry_run)
+            .context("Error switching to default session")?;
+    }
 
+    let sessionizer = sessionizer(dry_run);
     for session in sessions {
-        utils::tmux_kill_session(&session)
+        sessionizer
+            .kill(&session)
             .with_context(|| format!("Error killing current session: '{session}'"))?;
     }
 
     Ok(())
 }
+
+fn sessionizer(dry_run: bool) -> Sessionizer {
+    Sessionizer::new(Options {
+        dry_run,
+        ..Options::default()
+    })
+}
@@ -0,0 +1,39 @@
+//!
+//! # Last session handler
+//!
+//! This module handles the logic to toggle back to the previously used session.
+
+use crate::{
+    error::{Context, Result},
+    utils,
+};
+
+/// # Errors
+///
+/// Will return `Err` if the previously used session can't be read or any of the tmux operations
+/// fail.
+pub fn last(dry_run: bool) -> Result<()> {
+    let Some(last_session) = utils::last_session().context("Error reading last session")? else {
+        return utils::tmux_display_message("No previously used session", dry_run)
+            .context("Error sending 'No previously used session' notification");
+    };
+
+    let last_session_exists = utils::tmux_session_exisits(&last_session)
+        .with_context(|| format!("Error checking if session '{last_session}' exists"))?;
+    if !last_session_exists {
+        return utils::tmux_display_message(
+            &format!("The previously used session '{last_session}' isn't running anymore"),
+            dry_run,
+        )
+        .context("Error sending 'Last session not running' notification");
+    }
+
+    let previous_session = utils::session_to_remember(&last_session);
+    utils::tmux_enter_session(&last_session, None, dry_run)
+        .with_context(|| format!("Error switching to last session '{last_session}'"))?;
+    if let Some(previous_session) = previous_session {
+        utils::remember_last_session(&previous_session, dry_run);
+    }
+
+    Ok(())
+}
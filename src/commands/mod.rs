@@ -4,7 +4,12 @@
 //! This module provides the functionality that is used directly in the rost_sessionizer cli
 //! tool.
 
+pub mod bookmark;
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod kill;
+pub mod last;
 pub mod open;
+pub mod paths;
 pub mod startup;
+pub mod status;
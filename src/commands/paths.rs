@@ -0,0 +1,107 @@
+//!
+//! # Paths handler
+//!
+//! This module handles the logic to manage the searched paths in the config file.
+
+use crate::{
+    config,
+    config_file::ConfigFile,
+    error::{Context, Error, Result},
+    utils,
+};
+use log::info;
+
+/// Adds `entry` to the paths in the config file.
+///
+/// The entry has the same form as an entry of `SESSIONIZER_PATHS`, a path optionally followed by
+/// `;`-separated options. A relative path is made absolute.
+///
+/// # Errors
+///
+/// Will return `Err` if the config file can't be read or written.
+pub fn add(entry: &str, dry_run: bool) -> Result<()> {
+    let entry = absolute_entry(entry)?;
+    let mut config_file = ConfigFile::load().context("Error loading config file")?;
+    if config_file.values("path").contains(&entry.as_str()) {
+        info!("'{entry}' is already configured");
+        return Ok(());
+    }
+
+    config_file.add("path", &entry);
+    config_file
+        .save(dry_run)
+        .context("Error saving config file")
+}
+
+/// Removes every entry for `path` from the paths in the config file, regardless of its options.
+///
+/// # Errors
+///
+/// Will return `Err` if `path` isn't configured in the config file or the file can't be read or
+/// written.
+pub fn remove(path: &str, dry_run: bool) -> Result<()> {
+    let entry = absolute_entry(path)?;
+    let path = entry_path(&entry);
+    let mut config_file = ConfigFile::load().context("Error loading config file")?;
+    if config_file.remove("path", |value| entry_path(value) == path) == 0 {
+        return Err(Error::Message(format!(
+            "'{path}' isn't a path in the config file"
+        )));
+    }
+
+    config_file
+        .save(dry_run)
+        .context("Error saving config file")
+}
+
+/// Returns all configured path entries, the ones from `SESSIONIZER_PATHS` are marked with a
+/// trailing comment since they can't be removed with [`remove`].
+///
+/// # Errors
+///
+/// Will return `Err` if the config file can't be read.
+pub fn list() -> Result<Vec<String>> {
+    let config_file = ConfigFile::load().context("Error loading config file")?;
+    let from_file = config_file.values("path");
+
+    let mut entries = config::path_entries().context("Error getting configured paths")?;
+    // The entries of the config file come last, everything before them is from the environment.
+    let from_environment = entries.len() - from_file.len();
+    for entry in &mut entries[..from_environment] {
+        entry.push_str("  # SESSIONIZER_PATHS");
+    }
+
+    Ok(entries)
+}
+
+/// Returns the paths of the entries in the config file, which can be removed with [`remove`].
+///
+/// # Errors
+///
+/// Will return `Err` if the config file can't be read.
+pub fn removable() -> Result<Vec<String>> {
+    let config_file = ConfigFile::load().context("Error loading config file")?;
+
+    Ok(config_file
+        .values("path")
+        .into_iter()
+        .map(|entry| entry_path(entry).to_string())
+        .collect())
+}
+
+fn entry_path(entry: &str) -> &str {
+    entry.split(';').next().unwrap_or_default()
+}
+
+fn absolute_entry(entry: &str) -> Result<String> {
+    let (path, options) = match entry.split_once(';') {
+        Some((path, options)) => (path, Some(options)),
+        None => (entry, None),
+    };
+    let path = utils::absolute_path(path)?;
+
+    Ok(match options {
+        Some(options) => format!("{path};{options}"),
+        None => path,
+    })
+}
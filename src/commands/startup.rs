@@ -1,11 +1,23 @@
-use crate::{config, utils};
-use anyhow::{Context, Result};
+use crate::{
+    config,
+    error::{Context, Result},
+    utils,
+};
 use std::env;
 
+/// Starts the default session or attaches to it if it's already running. Additional configured
+/// sessions are created in the background.
+///
+/// With `grouped` set, a terminal attaching to an already running default session gets its own
+/// session in the same group, so several terminals can view different windows.
+///
 /// # Errors
 ///
 /// Will return `Err` if any of the tmux operations fail.
-pub fn startup() -> Result<()> {
+pub fn startup(grouped: bool, dry_run: bool) -> Result<()> {
+    // Additional sessions are only brought up in the background.
+    create_startup_sessions(dry_run).context("Error creating startup sessions")?;
+
     match env::var("TMUX") {
         Ok(val) if !val.is_empty() => {
             let default_session_exists = utils::tmux_session_exisits(&config::default_session())
@@ -16,22 +28,27 @@ pub fn startup() -> Result<()> {
                     )
                 })?;
             if default_session_exists {
-                utils::tmux_display_message(&format!(
-                    "The default session '{}' is already running",
-                    &config::default_session()
-                ))
+                utils::tmux_display_message(
+                    &format!(
+                        "The default session '{}' is already running",
+                        &config::default_session()
+                    ),
+                    dry_run,
+                )
                 .context("Error sending 'Default session already running' notification")?;
             } else {
-                create_default_session().with_context(|| {
+                create_default_session(dry_run).with_context(|| {
                     format!(
                         "Error creating default session '{}'",
                         &config::default_session()
                     )
                 })?;
-                utils::tmux_switch_client(&config::default_session(), Some(1)).with_context(
+                // Additional windows are created in the background, so a new session is entered
+                // at its first window.
+                utils::tmux_switch_client(&config::default_session(), None, dry_run).with_context(
                     || {
                         format!(
-                            "Error switching to first window of default session '{}'",
+                            "Error switching to default session '{}'",
                             &config::default_session()
                         )
                     },
@@ -39,57 +56,114 @@ pub fn startup() -> Result<()> {
             }
         }
         _ => {
-            create_default_session().with_context(|| {
-                format!(
-                    "Error creating default session '{}'",
-                    &config::default_session()
-                )
-            })?;
-            utils::tmux_command_without_output(&[
-                "attach-session",
-                "-t",
-                &format!("{}:1", &config::default_session()),
-            ])
-            .with_context(|| {
-                format!(
-                    "Error attaching to default session '{}'",
-                    &config::default_session()
+            let default_session_exists = utils::tmux_session_exisits(&config::default_session())
+                .with_context(|| {
+                    format!(
+                        "Error checking if default session '{}' exists",
+                        &config::default_session()
+                    )
+                })?;
+            if !default_session_exists {
+                create_default_session(dry_run).with_context(|| {
+                    format!(
+                        "Error creating default session '{}'",
+                        &config::default_session()
+                    )
+                })?;
+            }
+
+            let target_session = if grouped && default_session_exists {
+                create_grouped_session(dry_run).with_context(|| {
+                    format!(
+                        "Error creating session grouped with default session '{}'",
+                        &config::default_session()
+                    )
+                })?
+            } else {
+                config::default_session()
+            };
+            // A running session keeps its active window, a new one starts at its first window.
+            let first_window = if default_session_exists {
+                None
+            } else {
+                Some(
+                    utils::tmux_base_index()
+                        .context("Error getting tmux base-index")?
+                        .to_string(),
                 )
-            })?;
+            };
+            utils::tmux_attach_session(&target_session, first_window.as_deref(), dry_run)
+                .with_context(|| format!("Error attaching to session '{target_session}'"))?;
         }
     }
 
     Ok(())
 }
 
-fn create_default_session() -> Result<()> {
-    let home = env::var("HOME").context("Error getting $HOME")?;
-    utils::tmux_command_without_output(&[
-        "new-session",
-        "-ds",
-        &config::default_session(),
-        "-c",
-        &home,
-    ])
-    .with_context(|| {
-        format!(
-            "Error creating default tmux session '{}'",
-            &config::default_session()
-        )
-    })?;
-    utils::tmux_command_without_output(&[
-        "new-window",
-        "-t",
-        &format!("{}:2", &config::default_session()),
-        "-c",
-        &home,
-    ])
-    .with_context(|| {
-        format!(
-            "Error creating second window for default session '{}'",
-            config::default_session()
-        )
-    })?;
+pub(crate) fn create_default_session(dry_run: bool) -> Result<()> {
+    let dir = config::default_session_dir().context("Error getting default session directory")?;
+    config::default_session_layout()
+        .create_session(&config::default_session(), &dir, &[], dry_run)
+        .with_context(|| {
+            format!(
+                "Error creating default tmux session '{}'",
+                &config::default_session()
+            )
+        })?;
+
+    Ok(())
+}
+
+fn create_startup_sessions(dry_run: bool) -> Result<()> {
+    for session in config::startup_sessions() {
+        let session_exists = utils::tmux_session_exisits(&session)
+            .with_context(|| format!("Error checking if session '{session}' exists"))?;
+        if session_exists {
+            continue;
+        }
+
+        let dir = config::startup_session_dir(&session)
+            .with_context(|| format!("Error getting directory of session '{session}'"))?;
+        config::startup_session_layout(&session)
+            .create_session(&session, &dir, &[], dry_run)
+            .with_context(|| format!("Error creating startup session '{session}'"))?;
+    }
 
     Ok(())
 }
+
+fn create_grouped_session(dry_run: bool) -> Result<String> {
+    let existing_sessions =
+        utils::existing_session_names().context("Error getting existing session names")?;
+    let grouped_session = (1..)
+        .map(|i| format!("{}-{i}", config::default_session()))
+        .find(|name| !existing_sessions.contains(name))
+        .context("Error finding a free name for the grouped session")?;
+
+    utils::tmux_command_without_output(
+        &[
+            "new-session",
+            "-d",
+            "-t",
+            &config::default_session(),
+            "-s",
+            &grouped_session,
+        ],
+        dry_run,
+    )
+    .with_context(|| format!("Error creating grouped session '{grouped_session}'"))?;
+    // Grouped sessions only exist for the terminal that attached to them.
+    utils::tmux_command_without_output(
+        &[
+            "set-option",
+            "-t",
+            &grouped_session,
+            "destroy-unattached",
+            "on",
+        ],
+        dry_run,
+    )
+    .with_context(|| format!("Error configuring grouped session '{grouped_session}'"))?;
+
+    Ok(grouped_session)
+}
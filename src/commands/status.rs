@@ -0,0 +1,77 @@
+//!
+//! # Status handler
+//!
+//! This module handles the logic to describe a session in a single line for status bars.
+
+use crate::{
+    error::{Context, Result},
+    utils,
+};
+use std::path::PathBuf;
+
+/// Format used if none is given. It leaves out `{dirty}`, since that runs `git status` on every
+/// refresh of the status line.
+pub const DEFAULT_FORMAT: &str = "{kind}:{branch} +{others}";
+
+/// Renders `format` for `session`, or the current session if none is given.
+///
+/// Supported placeholders are `{session}`, `{root}`, `{kind}`, `{branch}`, `{dirty}`,
+/// `{worktree}` and `{others}`. Root and kind are read from the environment the session was
+/// created with, so no configured path has to be scanned. The dirty marker is only computed if the
+/// format asks for it, since it needs `git status`.
+///
+/// # Errors
+///
+/// Will return `Err` if the session or its metadata can't be determined.
+pub fn status(session: Option<&str>, format: &str) -> Result<String> {
+    let session = match session {
+        Some(session) => session.to_string(),
+        None => utils::current_session().context("Error getting current session")?,
+    };
+
+    let environment = utils::tmux_session_environment(&session)
+        .with_context(|| format!("Error getting environment of session '{session}'"))?;
+    let variable = |key: &str| {
+        environment
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.clone())
+    };
+    let root = match variable("SESSIONIZER_ROOT") {
+        Some(root) => PathBuf::from(root),
+        None => utils::tmux_session_path(&session)
+            .with_context(|| format!("Error getting path of session '{session}'"))?,
+    };
+    let kind = variable("SESSIONIZER_KIND").unwrap_or_else(|| utils::path_kind(&root).to_string());
+
+    let branch = utils::git_head_branch(&root).unwrap_or_default();
+    let dirty = if format.contains("{dirty}") && utils::git_dirty(&root) {
+        "*"
+    } else {
+        ""
+    };
+    let worktree = if kind == "worktree" {
+        root.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    } else {
+        String::new()
+    };
+    let others = if format.contains("{others}") {
+        utils::existing_session_names()
+            .context("Error getting existing session names")?
+            .len()
+            .saturating_sub(1)
+    } else {
+        0
+    };
+
+    Ok(format
+        .replace("{session}", &session)
+        .replace("{root}", &root.to_string_lossy())
+        .replace("{kind}", &kind)
+        .replace("{branch}", &branch)
+        .replace("{dirty}", dirty)
+        .replace("{worktree}", &worktree)
+        .replace("{others}", &others.to_string()))
+}
@@ -1,4 +1,210 @@
-use std::{env, path::PathBuf};
+use crate::{
+    config_file::ConfigFile,
+    error::{Context, Error, Result},
+    layout::{Layout, Window},
+    sessionizer::SearchMode,
+    utils,
+};
+use log::{info, warn};
+use std::{env, path::PathBuf, time::Duration};
+
+/// A configured path together with what it contributes to the candidates.
+///
+/// Options follow the path separated by `;`, e.g. `~/code;kinds=repos,worktrees;depth=1;prefix=work/`.
+/// The path may start with `~`, use `$VAR` or `${VAR}` and be a glob pattern like `~/worktrees/*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Root {
+    pub(crate) path: PathBuf,
+    /// Which of dirs, repos and worktrees are searched for.
+    pub(crate) kinds: Vec<SearchMode>,
+    /// How many levels below the path are scanned, 0 scans the path itself.
+    pub(crate) depth: usize,
+    /// Prepended to the name of every candidate found.
+    pub(crate) prefix: String,
+}
+
+impl Root {
+    fn parse(entry: &str) -> Root {
+        let mut options = entry.split(';');
+        let mut root = Root {
+            path: PathBuf::from(options.next().unwrap_or_default()),
+            kinds: vec![SearchMode::Dirs, SearchMode::Repos, SearchMode::Worktrees],
+            depth: 0,
+            prefix: String::new(),
+        };
+
+        for option in options.filter(|option| !option.trim().is_empty()) {
+            match option.split_once('=') {
+                Some(("kinds", kinds)) => {
+                    root.kinds = kinds
+                        .split(',')
+                        .flat_map(|kind| match kind.trim() {
+                            "all" => {
+                                vec![SearchMode::Dirs, SearchMode::Repos, SearchMode::Worktrees]
+                            }
+                            "dirs" => vec![SearchMode::Dirs],
+                            "repos" => vec![SearchMode::Repos],
+                            "worktrees" => vec![SearchMode::Worktrees],
+                            kind => {
+                                warn!(
+                                    "Ignoring unknown kind '{kind}' of '{}'",
+                                    root.path.display()
+                                );
+                                Vec::new()
+                            }
+                        })
+                        .collect();
+                }
+                Some(("depth", depth)) => match depth.trim().parse() {
+                    Ok(depth) => root.depth = depth,
+                    Err(_) => warn!(
+                        "Ignoring invalid depth '{depth}' of '{}'",
+                        root.path.display()
+                    ),
+                },
+                Some(("prefix", prefix)) => root.prefix = prefix.to_string(),
+                _ => warn!(
+                    "Ignoring unknown option '{option}' of '{}'",
+                    root.path.display()
+                ),
+            }
+        }
+
+        root
+    }
+
+    /// Expands `~` and environment variables in the path.
+    ///
+    /// A path using an unset variable doesn't contribute anything, so the same configuration works
+    /// on machines that only have some of the paths.
+    fn expand(self) -> Option<Root> {
+        let path = expand_path(&self.path.to_string_lossy())?;
+
+        Some(Root {
+            path: PathBuf::from(path),
+            ..self
+        })
+    }
+
+    /// Returns `true` if the path is a glob pattern like `~/worktrees/*`.
+    pub(crate) fn is_pattern(&self) -> bool {
+        self.path.to_string_lossy().contains(['*', '?', '['])
+    }
+
+    /// Returns the directories the path stands for, the matches of a glob pattern or the path
+    /// itself.
+    ///
+    /// Resolving a pattern reads directories, which can hang on an unavailable mount, so this is
+    /// only done while scanning. A pattern without matches doesn't contribute anything.
+    pub(crate) fn resolve(&self) -> Result<Vec<PathBuf>> {
+        if !self.is_pattern() {
+            return Ok(vec![self.path.clone()]);
+        }
+
+        resolve_glob(&self.path.to_string_lossy())
+    }
+}
+
+fn resolve_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let literal = PathBuf::from(pattern);
+    let paths: Vec<PathBuf> = match glob::glob(pattern) {
+        Ok(paths) => paths
+            .filter_map(|path| match path {
+                Ok(path) => Some(path),
+                Err(error) => {
+                    warn!("Ignoring a match of '{pattern}': {error}");
+                    None
+                }
+            })
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) if literal.is_dir() => Vec::new(),
+        Err(error) => {
+            return Err(Error::Message(format!(
+                "Invalid pattern '{pattern}': {error}"
+            )));
+        }
+    };
+
+    if paths.is_empty() {
+        // Characters like `[` are valid in directory names, so the path may be meant literally.
+        if literal.is_dir() {
+            return Ok(vec![literal]);
+        }
+        info!("'{pattern}' doesn't match any directory");
+    }
+
+    Ok(paths)
+}
+
+fn expand_path(path: &str) -> Option<String> {
+    expand_path_with(path, |name| {
+        env::var_os(name).map(|value| value.to_string_lossy().to_string())
+    })
+}
+
+/// Expands a leading `~` and `$VAR` or `${VAR}`, looking variables up with `lookup`.
+///
+/// Returns `None` with a warning if a variable isn't set or a `${` isn't closed.
+fn expand_path_with(path: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut expanded = String::new();
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let Some(home) = lookup("HOME") else {
+                warn!("Ignoring '{path}', $HOME isn't set");
+                return None;
+            };
+            expanded.push_str(&home);
+            rest
+        }
+        _ => path,
+    };
+
+    let mut chars = rest.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '$' {
+            expanded.push(char);
+            continue;
+        }
+        let name = if chars.next_if_eq(&'{').is_some() {
+            let mut name = String::new();
+            let mut closed = false;
+            for char in chars.by_ref() {
+                if char == '}' {
+                    closed = true;
+                    break;
+                }
+                name.push(char);
+            }
+            if !closed || name.is_empty() {
+                warn!("Ignoring '{path}', it contains a malformed '${{'");
+                return None;
+            }
+            name
+        } else {
+            let mut name = String::new();
+            while let Some(char) =
+                chars.next_if(|char| char.is_ascii_alphanumeric() || *char == '_')
+            {
+                name.push(char);
+            }
+            if name.is_empty() {
+                expanded.push('$');
+                continue;
+            }
+            name
+        };
+        match lookup(&name) {
+            Some(value) => expanded.push_str(&value),
+            None => {
+                warn!("Ignoring '{path}', ${name} isn't set");
+                return None;
+            }
+        }
+    }
+
+    Some(expanded)
+}
 
 pub(crate) fn default_session() -> String {
     match env::var_os("DEFAULT_SESSION") {
@@ -7,9 +213,305 @@ pub(crate) fn default_session() -> String {
     }
 }
 
-pub(crate) fn paths() -> Vec<PathBuf> {
-    match env::var_os("SESSIONIZER_PATHS") {
-        Some(paths) => env::split_paths(&paths).collect(),
-        None => panic!("No PATHS configured"),
+/// Returns the entries of `SESSIONIZER_PATHS` followed by the paths in the config file.
+pub(crate) fn path_entries() -> Result<Vec<String>> {
+    let mut entries: Vec<String> = match env::var_os("SESSIONIZER_PATHS") {
+        Some(paths) => env::split_paths(&paths)
+            .filter(|entry| !entry.as_os_str().is_empty())
+            .map(|entry| entry.to_string_lossy().to_string())
+            .collect(),
+        None => Vec::new(),
+    };
+    let config_file = ConfigFile::load().context("Error loading config file")?;
+    entries.extend(
+        config_file
+            .values("path")
+            .into_iter()
+            .map(ToString::to_string),
+    );
+
+    Ok(entries)
+}
+
+pub(crate) fn roots() -> Result<Vec<Root>> {
+    let entries = path_entries()?;
+    if entries.is_empty() && env::var_os("SESSIONIZER_PATHS").is_none() {
+        return Err(Error::NoPathsConfigured);
+    }
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| Root::parse(entry).expand())
+        .collect())
+}
+
+pub(crate) fn bookmarks() -> Result<Vec<PathBuf>> {
+    let config_file = ConfigFile::load().context("Error loading config file")?;
+
+    Ok(config_file
+        .values("bookmark")
+        .into_iter()
+        .filter_map(expand_path)
+        .map(PathBuf::from)
+        .collect())
+}
+
+pub(crate) fn sources() -> Vec<String> {
+    match env::var("SESSIONIZER_SOURCES") {
+        Ok(sources) => sources
+            .lines()
+            .map(str::trim)
+            .filter(|source| !source.is_empty())
+            .map(ToString::to_string)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub(crate) fn scan_timeout() -> Duration {
+    env::var("SESSIONIZER_SCAN_TIMEOUT")
+        .ok()
+        .and_then(|seconds| seconds.trim().parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .map_or(Duration::from_secs(2), Duration::from_secs_f64)
+}
+
+pub(crate) fn display_warnings() -> bool {
+    matches!(
+        env::var("SESSIONIZER_DISPLAY_WARNINGS").as_deref(),
+        Ok("1" | "true" | "yes")
+    )
+}
+
+pub(crate) fn hook(event: &str) -> Option<String> {
+    env::var(format!("SESSIONIZER_HOOK_{}", event.to_uppercase()))
+        .ok()
+        .filter(|hook| !hook.trim().is_empty())
+}
+
+pub(crate) fn session_env() -> Vec<(String, String)> {
+    match env::var("SESSIONIZER_SESSION_ENV") {
+        Ok(variables) => variables
+            .lines()
+            .filter_map(|variable| variable.trim().split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub(crate) fn load_dotenv() -> bool {
+    matches!(
+        env::var("SESSIONIZER_DOTENV").as_deref(),
+        Ok("1" | "true" | "yes")
+    )
+}
+
+pub(crate) fn editor() -> Option<String> {
+    ["SESSIONIZER_EDITOR", "VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|editor| !editor.trim().is_empty())
+}
+
+pub(crate) fn project_layout() -> Layout {
+    match env::var("SESSIONIZER_LAYOUT") {
+        Ok(layout) => Layout::parse(&layout),
+        Err(_) => Layout {
+            windows: vec![
+                Window {
+                    command: editor(),
+                    name: editor().as_deref().map(utils::command_name),
+                },
+                Window {
+                    name: Some("Bash".to_string()),
+                    command: None,
+                },
+            ],
+        },
+    }
+}
+
+pub(crate) fn default_session_dir() -> Result<PathBuf> {
+    match env::var_os("SESSIONIZER_DEFAULT_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => env::var_os("HOME")
+            .map(PathBuf::from)
+            .context("Error getting $HOME"),
+    }
+}
+
+pub(crate) fn default_session_layout() -> Layout {
+    match env::var("SESSIONIZER_DEFAULT_LAYOUT") {
+        Ok(layout) => Layout::parse(&layout),
+        Err(_) => Layout {
+            windows: vec![Window::default(); 2],
+        },
+    }
+}
+
+pub(crate) fn startup_sessions() -> Vec<String> {
+    match env::var("SESSIONIZER_STARTUP_SESSIONS") {
+        Ok(sessions) => sessions
+            .split(':')
+            .map(str::trim)
+            .filter(|session| !session.is_empty() && *session != default_session())
+            .map(ToString::to_string)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub(crate) fn startup_session_dir(session: &str) -> Result<PathBuf> {
+    match env::var_os(session_key(session, "DIR")) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => default_session_dir(),
+    }
+}
+
+pub(crate) fn startup_session_layout(session: &str) -> Layout {
+    match env::var(session_key(session, "LAYOUT")) {
+        Ok(layout) => Layout::parse(&layout),
+        Err(_) => Layout::default(),
+    }
+}
+
+fn session_key(session: &str, key: &str) -> String {
+    let session = session
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    format!("SESSIONIZER_SESSION_{session}_{key}")
+}
+
+pub(crate) fn state_dir() -> Result<PathBuf> {
+    let state_home = match env::var_os("XDG_STATE_HOME") {
+        Some(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("state"))
+            .context("Error getting $HOME")?,
+    };
+
+    Ok(state_home.join("rost_sessionizer"))
+}
+
+pub(crate) fn config_dir() -> Result<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".config"))
+            .context("Error getting $HOME")?,
+    };
+
+    Ok(config_home.join("rost_sessionizer"))
+}
+
+pub(crate) fn cache_dir() -> Result<PathBuf> {
+    let cache_home = match env::var_os("XDG_CACHE_HOME") {
+        Some(cache_home) if !cache_home.is_empty() => PathBuf::from(cache_home),
+        _ => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".cache"))
+            .context("Error getting $HOME")?,
+    };
+
+    Ok(cache_home.join("rost_sessionizer"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/user".to_string()),
+            "WORK" => Some("/work".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expand_path_expands_home_and_variables() {
+        assert_eq!(expand_path_with("~", lookup).as_deref(), Some("/home/user"));
+        assert_eq!(
+            expand_path_with("~/code", lookup).as_deref(),
+            Some("/home/user/code")
+        );
+        assert_eq!(
+            expand_path_with("$WORK/*", lookup).as_deref(),
+            Some("/work/*")
+        );
+        assert_eq!(
+            expand_path_with("${WORK}s/x", lookup).as_deref(),
+            Some("/works/x")
+        );
+        assert_eq!(
+            expand_path_with("$HOME-$WORK", lookup).as_deref(),
+            Some("/home/user-/work")
+        );
+    }
+
+    #[test]
+    fn expand_path_keeps_literal_characters() {
+        assert_eq!(expand_path_with("/a~b", lookup).as_deref(), Some("/a~b"));
+        assert_eq!(
+            expand_path_with("~user/x", lookup).as_deref(),
+            Some("~user/x")
+        );
+        assert_eq!(
+            expand_path_with("/cost/$/x", lookup).as_deref(),
+            Some("/cost/$/x")
+        );
+        assert_eq!(expand_path_with("/end$", lookup).as_deref(), Some("/end$"));
+    }
+
+    #[test]
+    fn expand_path_rejects_unset_and_malformed_variables() {
+        assert_eq!(expand_path_with("$UNSET/x", lookup), None);
+        assert_eq!(expand_path_with("${UNSET}", lookup), None);
+        assert_eq!(expand_path_with("${HOME", lookup), None);
+        assert_eq!(expand_path_with("/x/${}", lookup), None);
+        assert_eq!(expand_path_with("~", |_| None), None);
+    }
+
+    #[test]
+    fn root_parse_uses_defaults() {
+        assert_eq!(
+            Root::parse("/code"),
+            Root {
+                path: PathBuf::from("/code"),
+                kinds: vec![SearchMode::Dirs, SearchMode::Repos, SearchMode::Worktrees],
+                depth: 0,
+                prefix: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn root_parse_reads_options() {
+        assert_eq!(
+            Root::parse("~/worktrees/*;kinds=worktrees, repos;depth=2;prefix=work/;"),
+            Root {
+                path: PathBuf::from("~/worktrees/*"),
+                kinds: vec![SearchMode::Worktrees, SearchMode::Repos],
+                depth: 2,
+                prefix: "work/".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn root_parse_ignores_invalid_options() {
+        let root = Root::parse("/code;kinds=dirs,bogus;depth=deep;unknown=1;prefix");
+
+        assert_eq!(root.kinds, vec![SearchMode::Dirs]);
+        assert_eq!(root.depth, 0);
+        assert_eq!(root.prefix, "");
     }
 }
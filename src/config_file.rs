@@ -0,0 +1,138 @@
+//!
+//! # Config file section
+//!
+//! This module reads and edits the configuration file written by the `paths` and `bookmark`
+//! commands.
+//!
+//! The file is a plain list of `<key> <value>` lines, e.g. `path ~/code;depth=1` or
+//! `bookmark /home/user/notes`. Empty lines and lines starting with `#` are kept as they are.
+
+use crate::{
+    config,
+    error::{Context, Result},
+};
+use std::{fs, path::PathBuf};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConfigFile {
+    lines: Vec<String>,
+}
+
+impl ConfigFile {
+    fn file() -> Result<PathBuf> {
+        Ok(config::config_dir()
+            .context("Error getting config directory")?
+            .join("config"))
+    }
+
+    pub(crate) fn load() -> Result<ConfigFile> {
+        let file = ConfigFile::file()?;
+        if !file.try_exists()? {
+            return Ok(ConfigFile::default());
+        }
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Error reading '{}'", file.display()))?;
+
+        Ok(ConfigFile {
+            lines: content.lines().map(ToString::to_string).collect(),
+        })
+    }
+
+    /// Writes the file, with `dry_run` its new content is only printed.
+    pub(crate) fn save(&self, dry_run: bool) -> Result<()> {
+        let file = ConfigFile::file()?;
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+        if dry_run {
+            println!("# write '{}':", file.display());
+            print!("{content}");
+            return Ok(());
+        }
+
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Error creating '{}'", dir.display()))?;
+        }
+        // The file may be edited by hand as well, so an interrupted save mustn't truncate it. A
+        // symlinked file, e.g. from a dotfiles repository, is replaced at its target.
+        let file = fs::canonicalize(&file).unwrap_or(file);
+        let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, content).with_context(|| format!("Error writing '{}'", tmp.display()))?;
+        fs::rename(&tmp, &file).with_context(|| format!("Error replacing '{}'", file.display()))?;
+
+        Ok(())
+    }
+
+    /// Returns the values of all lines with `key`, in the order they appear in the file.
+    pub(crate) fn values(&self, key: &str) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| parse_line(line))
+            .filter(|(line_key, _)| *line_key == key)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    pub(crate) fn add(&mut self, key: &str, value: &str) {
+        self.lines.push(format!("{key} {value}"));
+    }
+
+    /// Removes every line with `key` whose value `matches`, returns how many were removed.
+    pub(crate) fn remove(&mut self, key: &str, matches: impl Fn(&str) -> bool) -> usize {
+        let before = self.lines.len();
+        self.lines.retain(|line| {
+            !parse_line(line).is_some_and(|(line_key, value)| line_key == key && matches(value))
+        });
+
+        before - self.lines.len()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    line.split_once(char::is_whitespace)
+        .map(|(key, value)| (key, value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_splits_key_and_value() {
+        assert_eq!(
+            parse_line("path ~/code;depth=1"),
+            Some(("path", "~/code;depth=1"))
+        );
+        assert_eq!(parse_line("  bookmark\t/a b  "), Some(("bookmark", "/a b")));
+    }
+
+    #[test]
+    fn parse_line_skips_comments_and_empty_values() {
+        assert_eq!(parse_line("# path /commented"), None);
+        assert_eq!(parse_line(""), None);
+        assert_eq!(parse_line("path"), None);
+        assert_eq!(parse_line("path   "), None);
+    }
+
+    #[test]
+    fn remove_keeps_other_lines() {
+        let mut config_file = ConfigFile {
+            lines: vec![
+                "# my paths".to_string(),
+                "path /a".to_string(),
+                "bookmark /a".to_string(),
+                "path /b".to_string(),
+            ],
+        };
+
+        assert_eq!(config_file.remove("path", |value| value == "/a"), 1);
+        assert_eq!(config_file.values("path"), vec!["/b"]);
+        assert_eq!(config_file.values("bookmark"), vec!["/a"]);
+        assert_eq!(config_file.lines[0], "# my paths");
+    }
+}
@@ -0,0 +1,162 @@
+//!
+//! # Error section
+//!
+//! This module provides the errors that can be inspected by callers.
+
+use std::{fmt::Display, io, path::PathBuf, process::ExitStatus, time::Duration};
+use thiserror::Error;
+
+/// Result type used throughout the library.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the library.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The user closed the picker without selecting anything.
+    #[error("Selection was cancelled")]
+    Cancelled,
+    /// There is no tmux server to talk to.
+    #[error("tmux isn't running")]
+    TmuxNotRunning,
+    /// Neither `SESSIONIZER_PATHS` nor the config file contain any paths.
+    #[error("No paths configured, set SESSIONIZER_PATHS or add one with `paths add`")]
+    NoPathsConfigured,
+    /// A session with this name is already running.
+    #[error("Session '{0}' already exists")]
+    SessionExists(String),
+    /// No client is attached to any session.
+    #[error("No current session available")]
+    NoCurrentSession,
+    /// The requested session or project is neither running nor configured.
+    #[error("'{0}' isn't a known session or project")]
+    UnknownTarget(String),
+    /// Scanning a configured path didn't finish in time, e.g. because it is on a hung mount.
+    #[error("Scanning '{}' timed out after {timeout:?}", root.display())]
+    ScanTimeout {
+        /// The configured path that was scanned.
+        root: PathBuf,
+        /// How long the scan was waited for.
+        timeout: Duration,
+    },
+    /// A source command didn't finish in time.
+    #[error("`{command}` timed out after {timeout:?}")]
+    SourceTimeout {
+        /// The command line that was executed.
+        command: String,
+        /// How long the command was waited for.
+        timeout: Duration,
+    },
+    /// A tmux command exited unsuccessfully.
+    #[error(transparent)]
+    Tmux(#[from] TmuxError),
+    /// A user configured command like a hook or a source exited unsuccessfully.
+    #[error("`{command}` exited with {status}")]
+    Command {
+        /// The command line that was executed.
+        command: String,
+        /// The exit status the command returned.
+        status: ExitStatus,
+    },
+    /// Spawning a process or accessing the filesystem failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The logger couldn't be installed.
+    #[error(transparent)]
+    Logger(#[from] log::SetLoggerError),
+    /// A value was missing or couldn't be parsed.
+    #[error("{0}")]
+    Message(String),
+    /// Another error with a description of what was being done.
+    #[error("{context}")]
+    Context {
+        /// What was being done when the error occurred.
+        context: String,
+        /// The underlying error.
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Returns the innermost error, skipping all added context.
+    #[must_use]
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Formats the error together with all of its sources.
+    pub(crate) fn describe(&self) -> String {
+        let mut description = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            description.push_str(&format!(": {error}"));
+            source = error.source();
+        }
+
+        description
+    }
+
+    /// Returns `true` if the user cancelled a selection, which isn't a failure.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.root(), Error::Cancelled)
+    }
+}
+
+/// A tmux command that ran but exited unsuccessfully.
+#[derive(Debug, Error)]
+#[error("`{command}` failed ({status}): {message}")]
+pub struct TmuxError {
+    /// The command line that was executed.
+    pub command: String,
+    /// The exit status tmux returned.
+    pub status: ExitStatus,
+    /// What tmux printed to stderr.
+    pub message: String,
+}
+
+/// Adds a description of what was being done to an error, similar to `anyhow::Context`.
+pub trait Context<T> {
+    /// Wraps the error with `context`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `self` is an error or empty.
+    fn context<C: Display>(self, context: C) -> Result<T>;
+
+    /// Wraps the error with the context returned by `f`, which is only evaluated on error.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `self` is an error or empty.
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|error| Error::Context {
+            context: context.to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|error| Error::Context {
+            context: f().to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| Error::Message(context.to_string()))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| Error::Message(f().to_string()))
+    }
+}
@@ -0,0 +1,136 @@
+//!
+//! # Layout section
+//!
+//! This module describes the windows of a session and creates sessions from them.
+
+use crate::{
+    error::{Context, Result},
+    utils,
+};
+use std::path::Path;
+
+/// A single window of a session layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Window {
+    pub name: Option<String>,
+    pub command: Option<String>,
+}
+
+/// The windows a session is created with, in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layout {
+    pub windows: Vec<Window>,
+}
+
+impl Layout {
+    /// Parses a layout in the form `name=command;name;=command`.
+    ///
+    /// Every `;` separated entry is a window. The part before `=` is the window name and the part
+    /// after it the command the window runs instead of a shell. Both can be left empty.
+    #[must_use]
+    pub fn parse(layout: &str) -> Layout {
+        let windows = layout
+            .split(';')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(|window| {
+                let (name, command) = window.split_once('=').unwrap_or((window, ""));
+                Window {
+                    name: Some(name.trim().to_string()).filter(|name| !name.is_empty()),
+                    command: Some(command.trim().to_string()).filter(|command| !command.is_empty()),
+                }
+            })
+            .collect();
+
+        Layout { windows }
+    }
+
+    pub(crate) fn create_session(
+        &self,
+        session: &str,
+        path: &Path,
+        environment: &[(String, String)],
+        dry_run: bool,
+    ) -> Result<()> {
+        let path = path
+            .to_str()
+            .context("Error converting session path to str")?;
+        let environment = environment
+            .iter()
+            .flat_map(|(key, value)| ["-e".to_string(), format!("{key}={value}")])
+            .collect::<Vec<String>>();
+        let mut windows = self.windows.iter();
+
+        // Create Session
+        let first_window = windows.next().cloned().unwrap_or_default();
+        let mut args = vec!["new-session", "-ds", session, "-c", path];
+        if let Some(name) = &first_window.name {
+            args.extend(["-n", name]);
+        }
+        args.extend(environment.iter().map(String::as_str));
+        if let Some(command) = &first_window.command {
+            args.push(command);
+        }
+        utils::tmux_command_without_output(&args, dry_run)
+            .with_context(|| format!("Error creating tmux session '{session}'"))?;
+
+        // Setup Window Layout
+        for window in windows {
+            let target = format!("{session}:");
+            let mut args = vec!["new-window", "-d", "-t", &target, "-c", path];
+            if let Some(name) = &window.name {
+                args.extend(["-n", name]);
+            }
+            if let Some(command) = &window.command {
+                args.push(command);
+            }
+            utils::tmux_command_without_output(&args, dry_run)
+                .with_context(|| format!("Error creating window in session '{session}'"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(name: Option<&str>, command: Option<&str>) -> Window {
+        Window {
+            name: name.map(ToString::to_string),
+            command: command.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn parse_reads_names_and_commands() {
+        assert_eq!(
+            Layout::parse("editor=nvim .; shell ;=htop").windows,
+            vec![
+                window(Some("editor"), Some("nvim .")),
+                window(Some("shell"), None),
+                window(None, Some("htop")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_keeps_equals_in_commands() {
+        assert_eq!(
+            Layout::parse("env=FOO=bar make").windows,
+            vec![window(Some("env"), Some("FOO=bar make"))]
+        );
+    }
+
+    #[test]
+    fn parse_skips_empty_windows() {
+        assert!(Layout::parse(" ; ;").windows.is_empty());
+        assert_eq!(
+            Layout::parse("=;a").windows,
+            vec![window(None, None), window(Some("a"), None)]
+        );
+    }
+}
@@ -3,11 +3,29 @@
 //!
 //! This handles the logic for the cli tool `rost_sessionizer`.
 
+/// Module that caches discovered candidates.
+mod cache;
+
 /// Module that handles the logic
 pub mod commands;
 
 /// Module that handles the configuration.
 pub mod config;
 
+/// Module that reads and edits the configuration file.
+mod config_file;
+
+/// Module that provides the error types.
+pub mod error;
+
+/// Module that handles session layouts.
+pub mod layout;
+
+/// Module that handles logging.
+pub mod logging;
+
+/// Module that provides the public API to embed the sessionizer.
+pub mod sessionizer;
+
 /// Module that provides internally used functions
 pub mod utils;
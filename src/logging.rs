@@ -0,0 +1,150 @@
+//!
+//! # Logging section
+//!
+//! This module provides a small `log` backend writing to stderr and optionally to a log file.
+//! Invocations from tmux key bindings have no visible terminal, so the log file is the only way to
+//! see what happened there.
+
+use crate::{
+    config,
+    error::{Context, Result},
+};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Records meant for stderr while it is held, see [`hold_stderr`].
+static HELD: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+struct Logger {
+    level: LevelFilter,
+    start: Instant,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let elapsed = self.start.elapsed().as_secs_f64() * 1000.0;
+        let line = format!(
+            "[{elapsed:>9.3}ms {:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        match HELD.lock().as_deref_mut() {
+            Ok(Some(held)) => held.push(line.clone()),
+            _ => eprintln!("{line}"),
+        }
+
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            let _ = writeln!(file, "{timestamp} pid={} {line}", std::process::id());
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file
+            && let Ok(mut file) = file.lock()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// Holds back the records written to stderr until the returned guard is dropped, while the log
+/// file still receives them right away.
+///
+/// A picker like fzf draws on the terminal, anything written to stderr meanwhile would corrupt it.
+pub(crate) fn hold_stderr() -> HeldStderr {
+    if let Ok(mut held) = HELD.lock() {
+        held.get_or_insert_with(Vec::new);
+    }
+
+    HeldStderr
+}
+
+/// Writes the held records to stderr when dropped, see [`hold_stderr`].
+pub(crate) struct HeldStderr;
+
+impl Drop for HeldStderr {
+    fn drop(&mut self) {
+        let held = HELD.lock().ok().and_then(|mut held| held.take());
+        for line in held.unwrap_or_default() {
+            eprintln!("{line}");
+        }
+    }
+}
+
+/// Maps the number of `-v` flags to a log level.
+#[must_use]
+pub fn level_from_verbosity(verbosity: u8) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Returns the log file used when `--log-file` is given without a path.
+///
+/// # Errors
+///
+/// Will return `Err` if the state directory can't be determined.
+pub fn default_log_file() -> Result<PathBuf> {
+    Ok(config::state_dir()
+        .context("Error getting state directory")?
+        .join("rost_sessionizer.log"))
+}
+
+/// Installs the logger. Records are always written to stderr and additionally appended to
+/// `log_file` if one is given.
+///
+/// # Errors
+///
+/// Will return `Err` if the log file can't be opened or a logger is already installed.
+pub fn init(level: LevelFilter, log_file: Option<&Path>) -> Result<()> {
+    let file = match log_file {
+        Some(log_file) => {
+            if let Some(dir) = log_file.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Error creating '{}'", dir.display()))?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .with_context(|| format!("Error opening log file '{}'", log_file.display()))?;
+            Some(Mutex::new(file))
+        }
+        None => None,
+    };
+
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        start: Instant::now(),
+        file,
+    }))
+    .context("Error installing logger")?;
+    log::set_max_level(level);
+
+    Ok(())
+}
@@ -6,39 +6,138 @@
 //! like working with *git worktrees*.
 
 use anyhow::{Context, Result};
-use clap_complete::Shell;
-use rost_sessionizer::commands::{
-    cli::{SearchMode, build_cli, print_completions},
-    kill, open, startup,
+use clap_complete::{CompleteEnv, Shell};
+use log::LevelFilter;
+use rost_sessionizer::{
+    commands::{
+        bookmark,
+        cli::{self, build_cli},
+        kill, last, open, paths, startup, status,
+    },
+    logging,
+    sessionizer::SearchMode,
 };
+use std::path::PathBuf;
 
 fn main() -> Result<()> {
+    // Answers completion requests from the scripts printed by `completions`.
+    CompleteEnv::with_factory(build_cli).complete();
+
     let args = build_cli().get_matches();
 
-    if let Some(generator) = args.get_one::<Shell>("generator").copied() {
-        let mut cmd = build_cli();
-        eprintln!("Generating completion file for {generator}...");
-        print_completions(generator, &mut cmd);
-    }
+    let log_file = match args.get_one::<PathBuf>("log-file") {
+        Some(log_file) => Some(log_file.clone()),
+        None if args.contains_id("log-file") => {
+            Some(logging::default_log_file().context("Error getting default log file")?)
+        }
+        None => None,
+    };
+    let level = match args.get_count("verbose") {
+        // Logging into a file is only requested for diagnostics, so be more detailed by default.
+        0 if log_file.is_some() => LevelFilter::Debug,
+        verbosity => logging::level_from_verbosity(verbosity),
+    };
+    logging::init(level, log_file.as_deref()).context("Error setting up logging")?;
+    let dry_run = args.get_flag("dry-run");
 
     match args.subcommand() {
         Some(("open", sub_matches)) => {
-            let _verbose = sub_matches.get_flag("verbose");
             let search_mode = sub_matches
                 .get_many::<SearchMode>("search")
-                .expect("default ensures there is always a value");
-            open::open(search_mode).context("Error while running the open command")?;
+                .expect("default ensures there is always a value")
+                .copied()
+                .collect::<Vec<_>>();
+            let target = sub_matches.get_one::<String>("target").map(String::as_str);
+            let refresh = sub_matches.get_flag("refresh");
+            match open::open(&search_mode, refresh, dry_run, target) {
+                // Closing the picker isn't an error.
+                Err(error) if error.is_cancelled() => {}
+                result => result.context("Error while running the open command")?,
+            }
         }
-        Some(("kill", _sub_matches)) => {
-            kill::kill_current_session().context("Error while trying to kill current session")?;
+        Some(("last", _sub_matches)) => {
+            last::last(dry_run).context("Error while switching to the last session")?;
         }
+        Some(("kill", sub_matches)) => match sub_matches.get_one::<String>("session") {
+            Some(session) => {
+                kill::kill_session(session, dry_run)
+                    .context("Error while trying to kill session")?;
+            }
+            None => {
+                kill::kill_current_session(dry_run)
+                    .context("Error while trying to kill current session")?;
+            }
+        },
         Some(("kill-all", _sub_matches)) => {
-            kill::kill_all_sessions().context("Error while trying to kill all sessions")?;
+            kill::kill_all_sessions(dry_run).context("Error while trying to kill all sessions")?;
+        }
+        Some(("startup", sub_matches)) => {
+            let grouped = sub_matches.get_flag("grouped");
+            startup::startup(grouped, dry_run)
+                .context("Error while starting default tmux session")?;
+        }
+        Some(("status", sub_matches)) => {
+            let session = sub_matches.get_one::<String>("session").map(String::as_str);
+            let format = sub_matches
+                .get_one::<String>("format")
+                .expect("default ensures there is always a value");
+            println!(
+                "{}",
+                status::status(session, format).context("Error while getting the status")?
+            );
+        }
+        Some(("paths", sub_matches)) => match sub_matches.subcommand() {
+            Some(("add", sub_matches)) => {
+                let path = sub_matches
+                    .get_one::<String>("path")
+                    .expect("path is a required argument");
+                paths::add(path, dry_run).context("Error while adding path")?;
+            }
+            Some(("remove", sub_matches)) => {
+                let path = sub_matches
+                    .get_one::<String>("path")
+                    .expect("path is a required argument");
+                paths::remove(path, dry_run).context("Error while removing path")?;
+            }
+            Some(("list", _sub_matches)) => {
+                for entry in paths::list().context("Error while listing paths")? {
+                    println!("{entry}");
+                }
+            }
+            e => unreachable!("Should be unreachable!: {:?}", e),
+        },
+        Some(("bookmark", sub_matches)) => match sub_matches.subcommand() {
+            Some(("add", sub_matches)) => {
+                let path = sub_matches
+                    .get_one::<String>("path")
+                    .expect("default ensures there is always a value");
+                bookmark::add(path, dry_run).context("Error while adding bookmark")?;
+            }
+            Some(("remove", sub_matches)) => {
+                let path = sub_matches
+                    .get_one::<String>("path")
+                    .expect("default ensures there is always a value");
+                bookmark::remove(path, dry_run).context("Error while removing bookmark")?;
+            }
+            Some(("list", _sub_matches)) => {
+                for path in bookmark::list().context("Error while listing bookmarks")? {
+                    println!("{path}");
+                }
+            }
+            e => unreachable!("Should be unreachable!: {:?}", e),
+        },
+        Some(("completions", sub_matches)) => {
+            let shell = sub_matches
+                .get_one::<Shell>("shell")
+                .copied()
+                .expect("shell is a required argument");
+            cli::print_completions(shell).context("Error while printing completions")?;
         }
-        Some(("startup", _sub_matches)) => {
-            startup::startup().context("Error while starting default tmux session")?;
+        Some(("man", sub_matches)) => {
+            let out_dir = sub_matches.get_one::<PathBuf>("out-dir");
+            cli::print_man_pages(out_dir.map(PathBuf::as_path))
+                .context("Error while generating man pages")?;
         }
-        None => println!("Generated bash completion script"),
         e => unreachable!("Should be unreachable!: {:?}", e),
     }
 
@@ -0,0 +1,1057 @@
+//!
+//! # Sessionizer
+//!
+//! This module provides the public API to discover candidates and open or kill their sessions.
+//! It is what the cli commands are built on and can be used to embed the sessionizer.
+
+use crate::{
+    cache::{self, Cache},
+    commands::startup,
+    config::{self, Root},
+    error::{Context, Error, Result},
+    logging, utils,
+};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Display},
+    io::Write,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+/// What should be searchable from the configured paths, every path can further limit the kinds it
+/// contributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SearchMode {
+    All,
+    Dirs,
+    Repos,
+    Worktrees,
+    Sources,
+}
+
+/// Options controlling which candidates a [`Sessionizer`] offers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Options {
+    pub search_modes: Vec<SearchMode>,
+    /// Rescan every configured path instead of using the candidate cache.
+    pub refresh: bool,
+    /// Print the tmux commands, hooks and state changes that would be executed instead of running
+    /// them. Commands that only query tmux still run so the printed plan matches reality.
+    pub dry_run: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            search_modes: vec![SearchMode::All],
+            refresh: false,
+            dry_run: false,
+        }
+    }
+}
+
+/// Something that can be opened as a session.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candidate {
+    pub name: String,
+    /// The directory a new session is created in. Running sessions that don't belong to a
+    /// discovered directory don't have one.
+    pub path: Option<PathBuf>,
+}
+
+impl Candidate {
+    /// Returns `true` if this candidate is an already running session.
+    #[must_use]
+    pub fn is_session(&self) -> bool {
+        self.path.is_none()
+    }
+}
+
+impl From<(String, Option<PathBuf>)> for Candidate {
+    fn from((name, path): (String, Option<PathBuf>)) -> Candidate {
+        Candidate { name, path }
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// Entry point for embedding the sessionizer.
+#[derive(Debug, Clone, Default)]
+pub struct Sessionizer {
+    options: Options,
+    discovery: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Sessionizer {
+    #[must_use]
+    pub fn new(options: Options) -> Sessionizer {
+        Sessionizer {
+            options,
+            discovery: Arc::default(),
+        }
+    }
+
+    /// Waits until the discovery started by [`Sessionizer::pick`] has finished, skipped paths
+    /// have been reported and the candidate cache has been saved.
+    ///
+    /// Not waiting is fine, the next discovery rescans whatever wasn't saved.
+    pub fn wait_for_discovery(&self) {
+        let handle = self
+            .discovery
+            .lock()
+            .ok()
+            .and_then(|mut discovery| discovery.take());
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+
+    /// Returns the names of all running sessions.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the sessions can't be listed.
+    pub fn sessions(&self) -> Result<Vec<String>> {
+        utils::existing_session_names().context("Error getting existing session names")
+    }
+
+    /// Returns the running sessions followed by everything discovered for the configured search
+    /// modes, with the default session first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the existing sessions can't be found or no paths are configured.
+    pub fn candidates(&self) -> Result<Vec<Candidate>> {
+        let mut possible_selections = self.session_selections()?;
+
+        // Paths are scanned in parallel, so merge in configuration order to keep the earlier path
+        // winning when names collide. Cached candidates are sent first and replaced by the results
+        // of a rescan.
+        let found: BTreeMap<usize, Dirs> = self.discover()?.into_iter().collect();
+        for dirs in found.into_values() {
+            possible_selections.try_extend(dirs);
+        }
+        info!("{} candidates available", possible_selections.len());
+
+        Ok(possible_selections.sort())
+    }
+
+    /// Returns the running sessions followed by the bookmarks and everything cached from earlier
+    /// discoveries, without scanning any path or running any source.
+    ///
+    /// This is fast enough for shell completion, but misses anything that wasn't discovered yet.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the existing sessions can't be found or the configuration can't be
+    /// read.
+    pub fn cached_candidates(&self) -> Result<Vec<Candidate>> {
+        let mut possible_selections = self.session_selections()?;
+        let cache = Cache::load().unwrap_or_else(|error| {
+            warn!("Error loading candidate cache: {error}");
+            Cache::default()
+        });
+        for job in self.jobs()? {
+            possible_selections.try_extend(job.cached(&cache));
+        }
+
+        Ok(possible_selections.sort())
+    }
+
+    /// Lets the user pick a candidate via fzf while discovery is still running.
+    ///
+    /// Running sessions are shown first, everything else is streamed into the picker as soon as
+    /// it is found.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::Cancelled`] if the picker is closed without a selection or `Err` if
+    /// no paths are configured or fzf can't be run.
+    pub fn pick(&self) -> Result<Candidate> {
+        let sessions = self.session_selections()?.sort();
+        let found = self.discover()?;
+
+        pick_via_fzf(sessions, found)
+    }
+
+    fn session_selections(&self) -> Result<Dirs> {
+        let session_names = self.sessions()?;
+
+        let mut possible_selections: Dirs = HashMap::new();
+        possible_selections
+            .entry(config::default_session())
+            .or_insert(None);
+
+        for session_name in session_names {
+            if session_name != config::default_session() {
+                possible_selections.entry(session_name).or_insert(None);
+            }
+        }
+
+        Ok(possible_selections)
+    }
+
+    /// Returns what has to be done to discover everything for the configured search modes, in
+    /// the order of precedence for colliding names.
+    fn jobs(&self) -> Result<Vec<Job>> {
+        let mut kinds = Vec::new();
+        let mut sources = false;
+        for search_mode in &self.options.search_modes {
+            match search_mode {
+                SearchMode::All => {
+                    kinds.extend([SearchMode::Dirs, SearchMode::Repos, SearchMode::Worktrees]);
+                    sources = true;
+                }
+                SearchMode::Sources => sources = true,
+                kind => kinds.push(*kind),
+            }
+        }
+        let mut seen = Vec::new();
+        kinds.retain(|kind| {
+            let first = !seen.contains(kind);
+            seen.push(*kind);
+            first
+        });
+
+        // Bookmarks are offered regardless of the configured paths and come first, so their names
+        // win over discovered ones.
+        let bookmarks = config::bookmarks()?;
+        let mut jobs = Vec::new();
+        if !bookmarks.is_empty() {
+            jobs.push(Job::Bookmarks(bookmarks));
+        }
+        if !kinds.is_empty() {
+            let roots = match config::roots() {
+                Err(Error::NoPathsConfigured) if !jobs.is_empty() => Vec::new(),
+                roots => roots?,
+            };
+            for kind in kinds {
+                let Some(scan) = kind.scan() else {
+                    continue;
+                };
+                // Every path only contributes the kinds configured for it.
+                jobs.extend(
+                    roots
+                        .iter()
+                        .filter(|root| root.kinds.contains(&kind))
+                        .map(|root| Job::Scan(scan, root.clone())),
+                );
+            }
+        }
+        if sources {
+            // Every source is run on its own, so one failing command doesn't hide the others.
+            jobs.extend(config::sources().into_iter().map(Job::Source));
+        }
+
+        Ok(jobs)
+    }
+
+    /// Scans all configured paths on a pool of worker threads and sends what they find.
+    ///
+    /// Cached results are sent first, a path whose signature changed is rescanned and its results
+    /// are sent again for the same index. A path that can't be scanned is reported as a warning
+    /// and skipped, so one unavailable mount doesn't hide all other candidates. The cache is saved
+    /// before the receiver is disconnected, see [`Sessionizer::wait_for_discovery`].
+    fn discover(&self) -> Result<Receiver<(usize, Dirs)>> {
+        let jobs = self.jobs()?;
+        // Workers pop from the back, so the first configured path is scanned first.
+        let jobs: Vec<(usize, Job)> = jobs.into_iter().enumerate().rev().collect();
+
+        // Even when refreshing, entries of paths and kinds that aren't scanned now are kept, only
+        // paths that aren't configured anymore are dropped.
+        let mut cache = Cache::load().unwrap_or_else(|error| {
+            warn!("Error loading candidate cache: {error}");
+            Cache::default()
+        });
+        match config::roots() {
+            Ok(roots) => cache.retain_roots(&roots),
+            Err(Error::NoPathsConfigured) => cache.retain_roots(&[]),
+            Err(error) => warn!("Error getting configured paths: {error}"),
+        }
+        let refresh = self.options.refresh;
+        let dry_run = self.options.dry_run;
+
+        let (sender, receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let cache = Mutex::new(cache);
+            let failures = Mutex::new(Vec::new());
+            let jobs = Mutex::new(jobs);
+            let workers = thread::available_parallelism().map_or(4, NonZeroUsize::get);
+
+            thread::scope(|scope| {
+                for _ in 0..workers {
+                    let sender = sender.clone();
+                    scope.spawn(|| {
+                        let sender = sender;
+                        while let Some((index, job)) =
+                            jobs.lock().ok().and_then(|mut jobs| jobs.pop())
+                        {
+                            let what = job.to_string();
+                            let send = |dirs| {
+                                let _ = sender.send((index, dirs));
+                            };
+                            match job.run(&cache, refresh, &send) {
+                                Ok(Some(found)) => {
+                                    // The receiver is gone once the picker was closed.
+                                    if sender.send((index, found)).is_err() {
+                                        break;
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(error) => {
+                                    warn!("Skipping {what}: {}", error.describe());
+                                    if let Ok(mut failures) = failures.lock() {
+                                        failures.push(what);
+                                    }
+                                }
+                            }
+                        }
+                    });
+                }
+            });
+            info!("Discovery finished in {:?}", start.elapsed());
+
+            let failures = failures.into_inner().unwrap_or_default();
+            if !failures.is_empty() && config::display_warnings() {
+                let message = format!("Skipped {}, see the log", failures.join(", "));
+                if let Err(error) = utils::tmux_display_message(&message, dry_run) {
+                    warn!("Error displaying skipped paths: {error}");
+                }
+            }
+
+            // A dry run doesn't change anything on disk.
+            if !dry_run && let Err(error) = cache.into_inner().unwrap_or_default().save() {
+                warn!("Error saving candidate cache: {error}");
+            }
+            // Only now the receiver sees the end, so waiting for all candidates includes the save.
+            drop(sender);
+        });
+        if let Ok(mut discovery) = self.discovery.lock() {
+            *discovery = Some(handle);
+        }
+
+        Ok(receiver)
+    }
+
+    /// Looks up the candidate called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::UnknownTarget`] if there is no such candidate.
+    pub fn find(&self, name: &str) -> Result<Candidate> {
+        self.candidates()?
+            .into_iter()
+            .find(|candidate| candidate.name == name)
+            .ok_or_else(|| Error::UnknownTarget(name.to_string()))
+    }
+
+    /// Lets the user pick one of `candidates` via fzf.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::Cancelled`] if the picker is closed without a selection or `Err` if
+    /// fzf can't be run.
+    pub fn select(&self, candidates: &[Candidate]) -> Result<Candidate> {
+        select_via_fzf(candidates)
+    }
+
+    /// Switches to the session of `candidate`, creating it first if it isn't running.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if any of the tmux operations fail.
+    pub fn open(&self, candidate: &Candidate) -> Result<()> {
+        self.open_window(candidate, None)
+    }
+
+    /// Like [`Sessionizer::open`], but switches to `window` instead of the session's active window.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if any of the tmux operations fail.
+    pub fn open_window(&self, candidate: &Candidate, window: Option<&str>) -> Result<()> {
+        let dry_run = self.options.dry_run;
+        let existing_session = utils::tmux_session_exisits(&candidate.name)
+            .with_context(|| format!("Error checking if session '{}' exists", candidate.name))?;
+
+        let default_session = candidate.name == config::default_session();
+
+        // Only running sessions can be asked for their path, a default session that isn't running
+        // yet is created in its configured directory instead.
+        let path = match &candidate.path {
+            Some(path) => path.clone(),
+            None if existing_session => utils::tmux_session_path(&candidate.name)
+                .with_context(|| format!("Error getting path of session '{}'", candidate.name))?,
+            None if default_session => {
+                config::default_session_dir().context("Error getting default session directory")?
+            }
+            None => return Err(Error::UnknownTarget(candidate.name.clone())),
+        };
+
+        if !existing_session {
+            if candidate.path.is_none() {
+                startup::create_default_session(dry_run)
+                    .context("Error creating default session")?;
+            } else {
+                create_tmux_session(candidate, dry_run)
+                    .context("Error creating new tmux session")?;
+            }
+            run_hook(utils::Hook::Create, &candidate.name, &path, dry_run);
+        }
+        run_hook(utils::Hook::Switch, &candidate.name, &path, dry_run);
+
+        // Outside of tmux the default session has to exist so detaching from a project doesn't
+        // kill the server.
+        if !utils::inside_tmux() && !default_session {
+            let default_session_exists = utils::tmux_session_exisits(&config::default_session())
+                .context("Error checking if default session exists")?;
+            if !default_session_exists {
+                startup::create_default_session(dry_run)
+                    .context("Error creating default session")?;
+            }
+        }
+        let previous_session = utils::session_to_remember(&candidate.name);
+        utils::tmux_enter_session(&candidate.name, window, dry_run)
+            .with_context(|| format!("Error entering session '{}'", candidate.name))?;
+        if let Some(previous_session) = previous_session {
+            utils::remember_last_session(&previous_session, dry_run);
+        }
+
+        Ok(())
+    }
+
+    /// Kills `session` after running the kill hook.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if any of the tmux operations fail.
+    pub fn kill(&self, session: &str) -> Result<()> {
+        let path = utils::tmux_session_path(session)
+            .with_context(|| format!("Error getting path of session '{session}'"))?;
+        run_hook(utils::Hook::Kill, session, &path, self.options.dry_run);
+
+        utils::tmux_kill_session(session, self.options.dry_run)
+    }
+}
+
+type Scan = fn(&Path) -> Result<Dirs>;
+
+const DIRECTORIES: (&str, Scan) = ("directories", directories_in);
+const REPOS: (&str, Scan) = ("repos", repos_in);
+const WORKTREES: (&str, Scan) = ("worktrees", worktrees_in);
+
+impl SearchMode {
+    fn scan(self) -> Option<(&'static str, Scan)> {
+        match self {
+            SearchMode::Dirs => Some(DIRECTORIES),
+            SearchMode::Repos => Some(REPOS),
+            SearchMode::Worktrees => Some(WORKTREES),
+            SearchMode::All | SearchMode::Sources => None,
+        }
+    }
+}
+
+enum Job {
+    Bookmarks(Vec<PathBuf>),
+    Scan((&'static str, Scan), Root),
+    Source(String),
+}
+
+impl Job {
+    /// Runs the job and returns what it found.
+    ///
+    /// A path with cached candidates hands them to `send` right away, before anything touches the
+    /// filesystem. It is only scanned afterwards if its signature changed, `None` is returned if the
+    /// cached candidates are still current.
+    fn run(self, cache: &Mutex<Cache>, refresh: bool, send: &dyn Fn(Dirs)) -> Result<Option<Dirs>> {
+        let start = Instant::now();
+        match self {
+            Job::Bookmarks(bookmarks) => Ok(Some(bookmark_dirs(bookmarks))),
+            Job::Scan((kind, scan), root) => {
+                let key = cache_kind(kind, &root);
+                let cached = if refresh {
+                    None
+                } else {
+                    cache
+                        .lock()
+                        .ok()
+                        .and_then(|cache| cache.get(&key, &root.path))
+                };
+                let cached_signature = cached.map(|(signature, found)| {
+                    debug!(
+                        "Found {} {kind} in '{}' (cached)",
+                        found.len(),
+                        root.path.display()
+                    );
+                    send(prefixed(
+                        &root,
+                        found
+                            .into_iter()
+                            .map(|(name, path)| (name, Some(path)))
+                            .collect(),
+                    ));
+                    signature
+                });
+
+                let timed_out = |timeout| Error::ScanTimeout {
+                    root: root.path.clone(),
+                    timeout,
+                };
+                // Resolving a pattern and checking the signature touch the filesystem as well, so
+                // they need the timeout.
+                let resolving = root.clone();
+                let (signature, scanned) = utils::with_timeout(timed_out, move || {
+                    let paths = resolving.resolve()?;
+                    let signature = cache::signature(&paths, resolving.depth);
+                    if cached_signature == Some(signature) {
+                        return Ok((signature, None));
+                    }
+                    Ok((signature, Some(scan_paths(scan, &resolving, &paths)?)))
+                })?;
+                let Some((found, complete)) = scanned else {
+                    debug!(
+                        "Cached {kind} in '{}' are current after {:?}",
+                        root.path.display(),
+                        start.elapsed()
+                    );
+                    return Ok(None);
+                };
+
+                if complete && let Ok(mut cache) = cache.lock() {
+                    cache.insert(&key, &root.path, signature, cache_entries(&found));
+                }
+                debug!(
+                    "Found {} {kind} in '{}' after {:?}",
+                    found.len(),
+                    root.path.display(),
+                    start.elapsed()
+                );
+
+                Ok(Some(prefixed(&root, found)))
+            }
+            Job::Source(source) => {
+                let dirs = get_source(&source)?;
+                debug!(
+                    "Found {} candidates from `{source}` after {:?}",
+                    dirs.len(),
+                    start.elapsed()
+                );
+
+                Ok(Some(dirs))
+            }
+        }
+    }
+
+    /// Returns what is known without touching the filesystem or running anything.
+    fn cached(self, cache: &Cache) -> Dirs {
+        match self {
+            Job::Bookmarks(bookmarks) => bookmark_dirs(bookmarks),
+            Job::Scan((kind, _), root) => match cache.get(&cache_kind(kind, &root), &root.path) {
+                Some((_, found)) => prefixed(
+                    &root,
+                    found
+                        .into_iter()
+                        .map(|(name, path)| (name, Some(path)))
+                        .collect(),
+                ),
+                None => Dirs::new(),
+            },
+            Job::Source(_) => Dirs::new(),
+        }
+    }
+}
+
+fn bookmark_dirs(bookmarks: Vec<PathBuf>) -> Dirs {
+    bookmarks
+        .into_iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((name, Some(path)))
+        })
+        .collect()
+}
+
+fn prefixed(root: &Root, dirs: Dirs) -> Dirs {
+    if root.prefix.is_empty() {
+        return dirs;
+    }
+
+    dirs.into_iter()
+        .map(|(name, path)| (format!("{}{name}", root.prefix), path))
+        .collect()
+}
+
+impl Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Job::Bookmarks(_) => write!(f, "bookmarks"),
+            Job::Scan((kind, _), root) => write!(f, "{kind} in '{}'", root.path.display()),
+            Job::Source(source) => write!(f, "source `{source}`"),
+        }
+    }
+}
+
+/// Returns the cache key for `kind`, results of different depths are cached separately.
+fn cache_kind(kind: &str, root: &Root) -> String {
+    if root.depth == 0 {
+        kind.to_string()
+    } else {
+        format!("{kind}/{}", root.depth)
+    }
+}
+
+/// Runs `scan` below every one of `paths`, which `root` resolved to.
+///
+/// A match of a pattern that can't be scanned is skipped with a warning, the returned flag is
+/// `false` then since incomplete results mustn't be cached. A plain path that can't be scanned is an
+/// error.
+fn scan_paths(scan: Scan, root: &Root, paths: &[PathBuf]) -> Result<(Dirs, bool)> {
+    let mut dirs = Dirs::new();
+    let mut complete = true;
+    for path in paths {
+        match scan_below(scan, path, root.depth) {
+            Ok(found) => dirs.try_extend(found),
+            Err(error) if root.is_pattern() => {
+                warn!("Skipping '{}': {}", path.display(), error.describe());
+                complete = false;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok((dirs, complete))
+}
+
+/// Runs `scan` on `path` itself or, with a `depth`, on every directory up to `depth` levels below.
+///
+/// Only `path` itself has to be readable, a directory below it that can't be scanned is skipped
+/// with a warning so it doesn't hide everything else.
+fn scan_below(scan: Scan, path: &Path, depth: usize) -> Result<Dirs> {
+    if depth == 0 {
+        return scan(path);
+    }
+
+    let mut dirs = Dirs::new();
+    let child_dirs = path
+        .read_dir()
+        .with_context(|| format!("Couldn't get the child directories of {}", path.display()))?;
+    for child_dir in child_dirs {
+        let dir = match child_dir.and_then(|dir| Ok((dir.file_type()?, dir))) {
+            Ok((file_type, dir)) if file_type.is_dir() => dir,
+            Ok(_) => continue,
+            Err(error) => {
+                warn!("Skipping an entry of '{}': {error}", path.display());
+                continue;
+            }
+        };
+        if dir.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let child = dir.path();
+        match scan(&child) {
+            Ok(found) => dirs.try_extend(found),
+            Err(error) => {
+                warn!("Skipping '{}': {}", child.display(), error.describe());
+                continue;
+            }
+        }
+        // Repositories are projects of their own, so their contents aren't searched.
+        if depth > 1 {
+            match child.join(".git").try_exists() {
+                Ok(true) => {}
+                Ok(false) => match scan_below(scan, &child, depth - 1) {
+                    Ok(found) => dirs.try_extend(found),
+                    Err(error) => warn!("Skipping '{}': {}", child.display(), error.describe()),
+                },
+                Err(error) => warn!("Skipping '{}': {error}", child.display()),
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Runs `hook` and only reports a failure, so a broken hook can't keep a session from being
+/// created, entered or killed.
+fn run_hook(hook: utils::Hook, session: &str, path: &Path, dry_run: bool) {
+    if let Err(error) = utils::run_hook(hook, session, Some(path), dry_run) {
+        warn!("{}", error.describe());
+    }
+}
+
+fn cache_entries(dirs: &Dirs) -> Vec<(String, PathBuf)> {
+    dirs.iter()
+        .filter_map(|(name, path)| Some((name.clone(), path.clone()?)))
+        .collect()
+}
+
+fn directories_in(path: &Path) -> Result<Dirs> {
+    let mut dirs: Dirs = Dirs::new();
+    if !path.join(".git").exists() {
+        dirs.entry(path.file_name().unwrap().to_string_lossy().to_string())
+            .or_insert(Some(path.to_path_buf()));
+    }
+
+    Ok(dirs)
+}
+
+fn repos_in(path: &Path) -> Result<Dirs> {
+    let mut dirs: Dirs = Dirs::new();
+    // Check if the configured path is a git repository.
+    if path.join(".git").exists() {
+        dirs.entry(path.file_name().unwrap().to_string_lossy().to_string())
+            .or_insert(Some(path.to_path_buf()));
+    }
+
+    Ok(dirs)
+}
+
+fn worktrees_in(path: &Path) -> Result<Dirs> {
+    let mut dirs: Dirs = Dirs::new();
+    // Check if the configured path is a bare git repository, find the worktrees and parse them.
+    let child_dirs = path
+        .canonicalize()
+        .with_context(|| format!("Couldn't resolve {}", path.display()))?
+        .read_dir()
+        .with_context(|| format!("Couldn't get the child directories of {}", &path.display()))?;
+    for child_dir in child_dirs {
+        let dir = child_dir.context("Child directory has an error")?;
+        if dir.file_type()?.is_dir() {
+            if let ".git" = dir
+                .file_name()
+                .to_str()
+                .context("Error converting filename to str")?
+            {
+                let mut path = dir.path();
+                path.pop();
+
+                dirs.entry(path.file_name().unwrap().to_string_lossy().to_string())
+                    .or_insert(Some(path.clone()));
+            } else {
+                let path = dir.path().join(".git");
+                if path.try_exists()? {
+                    let p = dir.path();
+                    let mut p = p.components().rev();
+                    let worktree = p
+                        .next()
+                        .context("Error getting worktree name")?
+                        .as_os_str()
+                        .to_string_lossy();
+                    let base = p
+                        .next()
+                        .context("Error getting base name")?
+                        .as_os_str()
+                        .to_string_lossy();
+                    dirs.entry(format!("{base}/{worktree}"))
+                        .or_insert(Some(dir.path()));
+                }
+            }
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Runs the source command `source` and parses its stdout, see [`parse_source_output`].
+///
+/// The command runs under the scan timeout, so a hung source can't block discovery. Its stderr is
+/// written to the log instead of the terminal the picker is drawn on.
+fn get_source(source: &str) -> Result<Dirs> {
+    let command = source.to_string();
+    let timed_out = |timeout| Error::SourceTimeout {
+        command: source.to_string(),
+        timeout,
+    };
+    let output = utils::with_timeout(timed_out, move || {
+        Command::new("sh")
+            .args(["-c", &command])
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Error running source command `{command}`"))
+    })?;
+    debug!("Source command `{source}` exited with {}", output.status);
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        info!("`{source}`: {line}");
+    }
+    if !output.status.success() {
+        return Err(Error::Command {
+            command: source.to_string(),
+            status: output.status,
+        });
+    }
+
+    Ok(parse_source_output(
+        source,
+        &String::from_utf8_lossy(&output.stdout),
+    ))
+}
+
+/// Parses the output of the source command `source`, every line is either `path` or
+/// `name<TAB>path`.
+///
+/// Paths have to be absolute, since there is no sensible directory to resolve relative ones
+/// against. Lines with a relative path are skipped with a warning.
+fn parse_source_output(source: &str, output: &str) -> Dirs {
+    let mut dirs: Dirs = Dirs::new();
+    for line in output.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let (name, path) = match line.split_once('\t') {
+            Some((name, path)) => (name.to_string(), PathBuf::from(path)),
+            None => {
+                let path = PathBuf::from(line);
+                let Some(name) = path.file_name() else {
+                    warn!("Skipping '{line}' of `{source}`, it has no name");
+                    continue;
+                };
+                (name.to_string_lossy().to_string(), path)
+            }
+        };
+        if !path.is_absolute() {
+            warn!("Skipping '{line}' of `{source}`, its path isn't absolute");
+            continue;
+        }
+        dirs.entry(name).or_insert(Some(path));
+    }
+
+    dirs
+}
+
+fn create_tmux_session(selected_session: &Candidate, dry_run: bool) -> Result<()> {
+    let path = selected_session.path.clone().unwrap();
+    let environment =
+        utils::session_environment(&path).context("Error building session environment")?;
+
+    config::project_layout()
+        .create_session(&selected_session.name, &path, &environment, dry_run)
+        .context("Error creating tmux session")?;
+
+    Ok(())
+}
+
+fn spawn_fzf() -> Result<Child> {
+    Command::new("fzf")
+        .args(["--margin=5%", "--padding=2%", "--border", "--ansi"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to spawn fzf")
+}
+
+fn write_candidate(stdin: &mut ChildStdin, candidate: &Candidate) -> std::io::Result<()> {
+    if candidate.is_session() {
+        // Display already open sessions in bold.
+        writeln!(stdin, "\x1b[1m{}\x1b[0m", candidate.name)
+    } else {
+        writeln!(stdin, "{}", candidate.name)
+    }
+}
+
+fn fzf_selection(child: Child) -> Result<String> {
+    let start = Instant::now();
+    let output = child
+        .wait_with_output()
+        .context("Error reading fzf stdout")?;
+    let selected = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // fzf exits with 130 if it was closed and with 1 if nothing matched when accepting.
+    match output.status.code() {
+        Some(130) => return Err(Error::Cancelled),
+        Some(1) if selected.is_empty() => return Err(Error::Cancelled),
+        _ => {}
+    }
+    debug!("Selected '{selected}' via fzf after {:?}", start.elapsed());
+
+    Ok(selected)
+}
+
+fn select_via_fzf(possible_selections: &[Candidate]) -> Result<Candidate> {
+    let mut child = spawn_fzf()?;
+
+    let stdin = child.stdin.as_mut().context("Error opening fzf stdin")?;
+    for possible_selection in possible_selections {
+        write_candidate(stdin, possible_selection)?;
+    }
+
+    let selected = fzf_selection(child)?;
+    let selected = possible_selections
+        .iter()
+        .find(|candidate| candidate.name == selected)
+        .context("Selected value isn't part of provided options")?;
+
+    Ok(selected.clone())
+}
+
+fn pick_via_fzf(sessions: Vec<Candidate>, found: Receiver<(usize, Dirs)>) -> Result<Candidate> {
+    // Discovery keeps logging while fzf is drawn on the terminal.
+    let held_stderr = logging::hold_stderr();
+    let mut child = spawn_fzf()?;
+    let mut stdin = child.stdin.take().context("Error opening fzf stdin")?;
+    // Everything found so far by the index of the job that found it. Names are offered as soon as
+    // they arrive, so colliding names are only resolved once one of them is selected.
+    let found_by_index: Arc<Mutex<BTreeMap<usize, Dirs>>> = Arc::default();
+
+    // Feed fzf from another thread, so a selection can be made before discovery has finished.
+    {
+        let sessions = sessions.clone();
+        let found_by_index = Arc::clone(&found_by_index);
+        thread::spawn(move || {
+            let mut offered = HashSet::new();
+            // Writing fails once fzf has exited, there is no one left to show more to.
+            let mut offer = |candidate: &Candidate| -> bool {
+                !offered.insert(candidate.name.clone())
+                    || write_candidate(&mut stdin, candidate).is_ok()
+            };
+
+            for session in &sessions {
+                if !offer(session) {
+                    return;
+                }
+            }
+            for (index, dirs) in found {
+                let candidates: Vec<Candidate> = dirs
+                    .iter()
+                    .map(|(name, path)| Candidate::from((name.clone(), path.clone())))
+                    .collect();
+                // A rescan replaces what was sent from the cache for the same path.
+                if let Ok(mut found_by_index) = found_by_index.lock() {
+                    found_by_index.insert(index, dirs);
+                }
+                for candidate in &candidates {
+                    if !offer(candidate) {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    let selected = fzf_selection(child);
+    drop(held_stderr);
+    let selected = selected?;
+
+    // Running sessions come first, otherwise the earliest configured path wins like in
+    // `candidates`.
+    if let Some(session) = sessions
+        .into_iter()
+        .find(|session| session.name == selected)
+    {
+        return Ok(session);
+    }
+    let found_by_index = found_by_index
+        .lock()
+        .map_err(|_| Error::Message("Offered candidates are poisoned".to_string()))?;
+    let path = found_by_index
+        .values()
+        .find_map(|dirs| dirs.get(&selected))
+        .context("Selected value isn't part of provided options")?;
+
+    Ok(Candidate::from((selected, path.clone())))
+}
+
+trait HashMapExtend {
+    fn try_extend(&mut self, iter: Self);
+
+    fn sort(&self) -> Vec<Candidate>;
+}
+
+type Dirs = HashMap<String, Option<PathBuf>>;
+
+impl HashMapExtend for Dirs {
+    fn try_extend(&mut self, iter: Self) {
+        for (k, v) in iter {
+            self.entry(k).or_insert(v);
+        }
+    }
+
+    fn sort(&self) -> Vec<Candidate> {
+        let mut sorted_vec: Vec<Candidate> = self
+            .iter()
+            .map(|(k, v)| Candidate::from((k.clone(), v.clone())))
+            .collect();
+        sorted_vec.sort_by(|a, b| {
+            if a.name == config::default_session() {
+                return std::cmp::Ordering::Less;
+            }
+            if b.name == config::default_session() {
+                return std::cmp::Ordering::Greater;
+            }
+
+            match (&a.path, &b.path) {
+                (None, Some(_)) => std::cmp::Ordering::Less,
+                (Some(_), None) => std::cmp::Ordering::Greater,
+                _ => a.name.cmp(&b.name),
+            }
+        });
+
+        sorted_vec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(entries: &[(&str, &str)]) -> Dirs {
+        entries
+            .iter()
+            .map(|(name, path)| ((*name).to_string(), Some(PathBuf::from(path))))
+            .collect()
+    }
+
+    #[test]
+    fn parse_source_output_reads_paths_and_names() {
+        let output = "/code/api\nweb\t/code/frontend\n\n/srv/docs/  \n";
+
+        assert_eq!(
+            parse_source_output("ghq list -p", output),
+            dirs(&[
+                ("api", "/code/api"),
+                ("web", "/code/frontend"),
+                ("docs", "/srv/docs/"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_source_output_skips_relative_and_nameless_paths() {
+        let output = "code/api\nweb\trelative\n/\n/code/ok\n";
+
+        assert_eq!(
+            parse_source_output("cat projects.txt", output),
+            dirs(&[("ok", "/code/ok")])
+        );
+    }
+
+    #[test]
+    fn parse_source_output_keeps_the_first_of_colliding_names() {
+        let output = "/a/api\n/b/api\napi\t/c/api\n";
+
+        assert_eq!(
+            parse_source_output("list", output),
+            dirs(&[("api", "/a/api")])
+        );
+    }
+}
@@ -3,55 +3,219 @@
 //!
 //! This module provides functions that are used internally.
 
-use anyhow::{Context, Result, anyhow};
-use std::process::Command;
+use crate::{
+    config,
+    error::{Context, Error, Result, TmuxError},
+};
+use log::{debug, trace, warn};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
 
-pub(crate) fn tmux_command_without_output(args: &[&str]) -> Result<()> {
-    Command::new("tmux")
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn command_line(args: &[&str]) -> String {
+    let args = args.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>();
+
+    format!("tmux {}", args.join(" "))
+}
+
+/// Runs a tmux command that changes something, with `dry_run` it is only printed.
+pub(crate) fn tmux_command_without_output(args: &[&str], dry_run: bool) -> Result<()> {
+    let command = command_line(args);
+    if dry_run {
+        println!("{command}");
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let output = Command::new("tmux")
         .args(args)
-        .status()
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(Child::wait_with_output)
         .with_context(|| format!("Error running tmux command `tmux {args:#?}"))?;
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    debug!(
+        "`{command}` exited with {} after {:?}{}",
+        output.status,
+        start.elapsed(),
+        if message.is_empty() {
+            String::new()
+        } else {
+            format!(": {message}")
+        }
+    );
+
+    if !output.status.success() {
+        if no_server(&output) {
+            return Err(Error::TmuxNotRunning);
+        }
+        if let Some(session) = message.strip_prefix("duplicate session: ") {
+            return Err(Error::SessionExists(session.to_string()));
+        }
+        return Err(TmuxError {
+            command,
+            status: output.status,
+            message,
+        }
+        .into());
+    }
 
     Ok(())
 }
 
-pub(crate) fn tmux_switch_client(target_session: &str, target_window: Option<usize>) -> Result<()> {
-    tmux_command_without_output(&[
-        "switch-client",
-        "-t",
-        &format!("{target_session}:{}", target_window.unwrap_or(1)),
-    ])
+fn tmux_command_with_output(args: &[&str]) -> Result<Output> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .with_context(|| format!("Error running tmux command `tmux {args:#?}"))?;
+    trace!("`tmux {}` exited with {}", args.join(" "), output.status);
+
+    Ok(output)
+}
+
+fn tmux_error(args: &[&str], output: &Output) -> TmuxError {
+    TmuxError {
+        command: command_line(args),
+        status: output.status,
+        message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    }
+}
+
+// A missing server just means there is nothing running yet.
+fn no_server(output: &Output) -> bool {
+    let message = String::from_utf8_lossy(&output.stderr);
+
+    message.starts_with("no server running") || message.starts_with("error connecting to")
+}
+
+pub(crate) fn tmux_switch_client(
+    target_session: &str,
+    target_window: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    tmux_command_without_output(
+        &[
+            "switch-client",
+            "-t",
+            &target(target_session, target_window),
+        ],
+        dry_run,
+    )
     .with_context(|| format!("Error switching tmux client to '{target_session}'"))?;
 
     Ok(())
 }
 
-pub(crate) fn tmux_kill_session(target_session: &str) -> Result<()> {
-    tmux_command_without_output(&["kill-session", "-t", target_session])
+pub(crate) fn tmux_attach_session(
+    target_session: &str,
+    target_window: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    tmux_command_without_output(
+        &[
+            "attach-session",
+            "-t",
+            &target(target_session, target_window),
+        ],
+        dry_run,
+    )
+    .with_context(|| format!("Error attaching to '{target_session}'"))?;
+
+    Ok(())
+}
+
+// Without a window the session's active window is kept.
+fn target(target_session: &str, target_window: Option<&str>) -> String {
+    match target_window {
+        Some(target_window) => format!("{target_session}:{target_window}"),
+        None => format!("{target_session}:"),
+    }
+}
+
+pub(crate) fn inside_tmux() -> bool {
+    env::var("TMUX").is_ok_and(|tmux| !tmux.is_empty())
+}
+
+pub(crate) fn tmux_enter_session(
+    target_session: &str,
+    target_window: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    if inside_tmux() {
+        tmux_switch_client(target_session, target_window, dry_run)
+    } else {
+        tmux_attach_session(target_session, target_window, dry_run)
+    }
+}
+
+pub(crate) fn tmux_base_index() -> Result<usize> {
+    let output = Command::new("tmux")
+        .args(["show-options", "-gv", "base-index"])
+        .output()
+        .context("Error reading tmux option 'base-index'")?
+        .stdout;
+    let base_index = String::from_utf8_lossy(&output);
+
+    // Without a running server tmux falls back to its default.
+    Ok(base_index.trim().parse().unwrap_or(0))
+}
+
+pub(crate) fn tmux_kill_session(target_session: &str, dry_run: bool) -> Result<()> {
+    tmux_command_without_output(&["kill-session", "-t", target_session], dry_run)
         .with_context(|| format!("Error killing session '{target_session}'"))?;
 
     Ok(())
 }
 
-pub(crate) fn tmux_display_message(message: &str) -> Result<()> {
-    tmux_command_without_output(&["display-message", message]).context("Error sending notification")
+pub(crate) fn tmux_display_message(message: &str, dry_run: bool) -> Result<()> {
+    tmux_command_without_output(&["display-message", message], dry_run)
+        .context("Error sending notification")
 }
 
 pub(crate) fn tmux_session_exisits(target_session: &str) -> Result<bool> {
-    Command::new("tmux")
-        .args(["has-session", "-t", target_session])
-        .status()
-        .map(|status| status.success())
-        .context("Error checking if session exists")
+    let args = ["has-session", "-t", target_session];
+    let output = tmux_command_with_output(&args).context("Error checking if session exists")?;
+    let message = String::from_utf8_lossy(&output.stderr);
+    let exists = output.status.success();
+    if !exists && !no_server(&output) && !message.starts_with("can't find session") {
+        return Err(tmux_error(&args, &output).into());
+    }
+    trace!("Session '{target_session}' exists: {exists}");
+
+    Ok(exists)
 }
 
 pub(crate) fn existing_sessions() -> Result<Vec<String>> {
-    let existing_sessions = Command::new("tmux")
-        .arg("ls")
-        .output()
-        .context("Error listing existing tmux sessions")?
-        .stdout;
-    let existing_sessions = String::from_utf8_lossy(&existing_sessions).to_string();
+    let args = ["ls"];
+    let output = tmux_command_with_output(&args).context("Error listing existing tmux sessions")?;
+    if !output.status.success() {
+        if no_server(&output) {
+            return Ok(Vec::new());
+        }
+        return Err(tmux_error(&args, &output).into());
+    }
+    let existing_sessions = String::from_utf8_lossy(&output.stdout).to_string();
+    trace!(
+        "`tmux ls` returned {} sessions",
+        existing_sessions.lines().count()
+    );
 
     Ok(existing_sessions
         .lines()
@@ -60,16 +224,24 @@ pub(crate) fn existing_sessions() -> Result<Vec<String>> {
 }
 
 pub(crate) fn current_session() -> Result<String> {
-    let existing_sessions = existing_sessions().context("Error getting existing sessions")?;
-    let current_session = existing_sessions
-        .iter()
-        .find(|s| s.contains("attached"))
-        .context("No current session available")?;
+    // Ask for the session of the client this was invoked from, with several attached clients any
+    // of them could be listed first by `list-sessions`.
+    let args = ["display-message", "-p", "#{session_name}"];
+    let output = tmux_command_with_output(&args).context("Error getting current session")?;
+    if !output.status.success() {
+        if no_server(&output) || String::from_utf8_lossy(&output.stderr).contains("no current") {
+            return Err(Error::NoCurrentSession);
+        }
+        return Err(tmux_error(&args, &output).into());
+    }
 
-    match current_session.find(':') {
-        Some(i) => Ok(current_session[..i].to_string()),
-        None => Err(anyhow!("Error parsing current session name")),
+    let current_session = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if current_session.is_empty() {
+        return Err(Error::NoCurrentSession);
     }
+    trace!("Current session is '{current_session}'");
+
+    Ok(current_session)
 }
 
 pub(crate) fn existing_session_names() -> Result<Vec<String>> {
@@ -86,3 +258,349 @@ pub(crate) fn existing_session_names() -> Result<Vec<String>> {
         })
         .collect())
 }
+
+pub(crate) fn command_name(command: &str) -> String {
+    let program = command.split_whitespace().next().unwrap_or(command);
+
+    Path::new(program).file_name().map_or_else(
+        || program.to_string(),
+        |name| name.to_string_lossy().to_string(),
+    )
+}
+
+/// Runs `f` on its own thread and gives up on it after the configured scan timeout, returning the
+/// error built by `timed_out`.
+///
+/// Work blocked on a hung mount can't be interrupted, so its thread is left behind and ends with
+/// the process.
+pub(crate) fn with_timeout<T: Send + 'static>(
+    timed_out: impl FnOnce(Duration) -> Error,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let timeout = config::scan_timeout();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(timed_out(timeout)),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(Error::Message("The worker thread panicked".to_string()))
+        }
+    }
+}
+
+pub(crate) fn absolute_path(path: &str) -> Result<String> {
+    // Paths using `~` or variables are kept as written, so they are expanded on every machine.
+    if path.starts_with(['/', '~', '$']) {
+        return Ok(path.to_string());
+    }
+    let absolute = env::current_dir()
+        .context("Error getting current directory")?
+        .join(path);
+    let absolute = match absolute.canonicalize() {
+        Ok(canonical) => canonical,
+        // Glob patterns and paths that don't exist yet can't be resolved.
+        Err(_) => absolute,
+    };
+
+    Ok(absolute.to_string_lossy().to_string())
+}
+
+pub(crate) fn tmux_session_path(target_session: &str) -> Result<PathBuf> {
+    let args = [
+        "display-message",
+        "-p",
+        "-t",
+        target_session,
+        "#{session_path}",
+    ];
+    let output = tmux_command_with_output(&args)
+        .with_context(|| format!("Error getting path of session '{target_session}'"))?;
+    if !output.status.success() {
+        return Err(tmux_error(&args, &output).into());
+    }
+
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Hook {
+    Create,
+    Switch,
+    Kill,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Create => "create",
+            Hook::Switch => "switch",
+            Hook::Kill => "kill",
+        }
+    }
+}
+
+pub(crate) fn tmux_session_environment(target_session: &str) -> Result<Vec<(String, String)>> {
+    let args = ["show-environment", "-t", target_session];
+    let output = tmux_command_with_output(&args)
+        .with_context(|| format!("Error getting environment of session '{target_session}'"))?;
+    if !output.status.success() {
+        return Err(tmux_error(&args, &output).into());
+    }
+
+    // Removed variables are listed as `-NAME` and don't have a value.
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+pub(crate) fn path_kind(path: &Path) -> &'static str {
+    let git = path.join(".git");
+    if git.is_dir() {
+        "repo"
+    } else if git.is_file() {
+        "worktree"
+    } else {
+        "dir"
+    }
+}
+
+pub(crate) fn run_hook(
+    hook: Hook,
+    session: &str,
+    path: Option<&Path>,
+    dry_run: bool,
+) -> Result<()> {
+    let Some(command) = config::hook(hook.name()) else {
+        return Ok(());
+    };
+
+    if dry_run {
+        println!(
+            "SESSIONIZER_EVENT={} SESSIONIZER_SESSION={} sh -c {}",
+            hook.name(),
+            shell_quote(session),
+            shell_quote(&command)
+        );
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", &command])
+        .env("SESSIONIZER_EVENT", hook.name())
+        .env("SESSIONIZER_SESSION", session);
+    if let Some(path) = path {
+        cmd.env("SESSIONIZER_PATH", path)
+            .env("SESSIONIZER_KIND", path_kind(path))
+            .current_dir(path);
+    }
+
+    let start = Instant::now();
+    let status = cmd
+        .status()
+        .with_context(|| format!("Error running {} hook `{command}`", hook.name()))?;
+    debug!(
+        "{} hook `{command}` exited with {status} after {:?}",
+        hook.name(),
+        start.elapsed()
+    );
+    if !status.success() {
+        return Err(Error::Command { command, status })
+            .context(format!("The {} hook failed", hook.name()));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn git_branch(path: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["branch", "--show-current"])
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    (output.status.success() && !branch.is_empty()).then_some(branch)
+}
+
+// Reads the checked out branch straight from the git directory so no process has to be spawned.
+pub(crate) fn git_head_branch(path: &Path) -> Option<String> {
+    let git = path.join(".git");
+    let git_dir = if git.is_file() {
+        let gitdir = fs::read_to_string(&git).ok()?;
+        path.join(gitdir.trim().strip_prefix("gitdir:")?.trim())
+    } else {
+        git
+    };
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+
+    match head.trim().strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        // Detached HEAD, show the abbreviated commit instead.
+        None => head.get(..7).map(ToString::to_string),
+    }
+}
+
+pub(crate) fn git_dirty(path: &Path) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+pub(crate) fn read_dotenv(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Error reading dotenv file '{}'", path.display()))?;
+
+    Ok(parse_dotenv(&content))
+}
+
+fn parse_dotenv(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("export ").unwrap_or(line).split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+pub(crate) fn session_environment(path: &Path) -> Result<Vec<(String, String)>> {
+    let mut environment = vec![
+        (
+            "SESSIONIZER_ROOT".to_string(),
+            path.to_string_lossy().to_string(),
+        ),
+        ("SESSIONIZER_KIND".to_string(), path_kind(path).to_string()),
+    ];
+    if let Some(branch) = git_branch(path) {
+        environment.push(("SESSIONIZER_BRANCH".to_string(), branch));
+    }
+    environment.extend(config::session_env());
+
+    let dotenv = path.join(".env");
+    if config::load_dotenv() && dotenv.is_file() {
+        environment.extend(read_dotenv(&dotenv).context("Error loading project .env file")?);
+    }
+
+    Ok(environment)
+}
+
+pub(crate) fn last_session() -> Result<Option<String>> {
+    let file = config::state_dir()
+        .context("Error getting state directory")?
+        .join("last_session");
+    if !file.try_exists()? {
+        return Ok(None);
+    }
+    let session =
+        fs::read_to_string(&file).with_context(|| format!("Error reading '{}'", file.display()))?;
+
+    Ok(Some(session.trim().to_string()).filter(|session| !session.is_empty()))
+}
+
+pub(crate) fn set_last_session(session: &str, dry_run: bool) -> Result<()> {
+    if dry_run {
+        println!("# remember {} as last session", shell_quote(session));
+        return Ok(());
+    }
+
+    let dir = config::state_dir().context("Error getting state directory")?;
+    fs::create_dir_all(&dir).with_context(|| format!("Error creating '{}'", dir.display()))?;
+    let file = dir.join("last_session");
+    fs::write(&file, session).with_context(|| format!("Error writing '{}'", file.display()))?;
+
+    Ok(())
+}
+
+/// Returns the current session if it isn't `target_session`, it becomes the last session once
+/// switching to `target_session` succeeded.
+pub(crate) fn session_to_remember(target_session: &str) -> Option<String> {
+    if !inside_tmux() {
+        return None;
+    }
+    match current_session() {
+        Ok(current_session) => Some(current_session).filter(|current| current != target_session),
+        Err(error) => {
+            warn!("Error getting current session: {}", error.describe());
+            None
+        }
+    }
+}
+
+/// Remembers `session` as the last session, a failure only costs the `last` command its target.
+pub(crate) fn remember_last_session(session: &str, dry_run: bool) {
+    if let Err(error) = set_last_session(session, dry_run) {
+        warn!("Error remembering the last session: {}", error.describe());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_dotenv_reads_assignments() {
+        let content = "# comment\n\nFOO=bar\nexport BAZ = qux \nEMPTY=\nURL=a=b\n";
+
+        assert_eq!(
+            parse_dotenv(content),
+            pairs(&[
+                ("FOO", "bar"),
+                ("BAZ", "qux"),
+                ("EMPTY", ""),
+                ("URL", "a=b")
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_dotenv_strips_matching_quotes() {
+        let content = "A=\"double quoted\"\nB='single quoted'\nC=\"unbalanced\nD='mixed\"";
+
+        assert_eq!(
+            parse_dotenv(content),
+            pairs(&[
+                ("A", "double quoted"),
+                ("B", "single quoted"),
+                ("C", "\"unbalanced"),
+                ("D", "'mixed\""),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_dotenv_skips_lines_without_assignment() {
+        assert!(parse_dotenv("just text\nexport\n").is_empty());
+    }
+}